@@ -0,0 +1,92 @@
+// Copyright 2022 System76 <info@system76.com>
+// SPDX-License-Identifier: MIT
+
+//! Compares `PState::values` with `PState::values_parallel`.
+//!
+//! Run with `cargo bench --features parallel`. The driver directory is read if this
+//! system has one, along with a copy of it in a temporary directory, and the copy
+//! again with a delay added to each read, as a stand-in for attributes which are slow
+//! to read.
+
+use intel_pstate::{PState, PStateBackend, SysfsBackend};
+use std::{
+    fs, io,
+    path::Path,
+    thread,
+    time::{Duration, Instant},
+};
+
+const ITERATIONS: u32 = 2_000;
+
+/// Reads files from the filesystem after waiting for `delay`.
+struct SlowBackend {
+    delay: Duration,
+}
+
+impl PStateBackend for SlowBackend {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        thread::sleep(self.delay);
+        SysfsBackend.read(path)
+    }
+
+    fn write(&self, path: &Path, value: &str) -> io::Result<()> {
+        SysfsBackend.write(path, value)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        SysfsBackend.exists(path)
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        SysfsBackend.is_dir(path)
+    }
+}
+
+fn time<T>(f: impl Fn() -> T) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    start.elapsed() / ITERATIONS
+}
+
+fn compare(name: &str, pstate: &PState) {
+    let serial = time(|| pstate.values().unwrap());
+    let parallel = time(|| pstate.values_parallel().unwrap());
+    println!(
+        "{:<24} values: {:>10.1?}  values_parallel: {:>10.1?}",
+        name, serial, parallel
+    );
+}
+
+fn main() {
+    if let Ok(pstate) = PState::new() {
+        compare("sysfs", &pstate);
+    }
+
+    let dir = tempfile::tempdir().unwrap();
+    let driver = dir.path().join("intel_pstate");
+    fs::create_dir(&driver).unwrap();
+    for &(file, value) in &[
+        ("hwp_dynamic_boost", "0\n"),
+        ("min_perf_pct", "20\n"),
+        ("max_perf_pct", "100\n"),
+        ("no_turbo", "0\n"),
+        ("status", "active\n"),
+    ] {
+        fs::write(driver.join(file), value).unwrap();
+    }
+
+    compare(
+        "tempdir",
+        &PState::with_backend(&driver, SysfsBackend).unwrap(),
+    );
+
+    for &micros in &[10, 100, 1000] {
+        let delay = Duration::from_micros(micros);
+        compare(
+            &format!("tempdir, {:?} per read", delay),
+            &PState::with_backend(&driver, SlowBackend { delay }).unwrap(),
+        );
+    }
+}
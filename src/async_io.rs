@@ -0,0 +1,271 @@
+// Copyright 2022 System76 <info@system76.com>
+// SPDX-License-Identifier: MIT
+
+//! Asynchronous variants of the `PState` getters and setters, which perform their
+//! IO on tokio's blocking thread pool.
+//!
+//! `spawn_blocking` is used rather than `tokio::fs` because all IO goes through the
+//! handle's [`PStateBackend`], which may not be the filesystem at all. `tokio::fs`
+//! itself runs each call on the same blocking pool, so nothing is lost by this.
+
+use crate::*;
+
+use tokio::task::spawn_blocking;
+
+impl PState {
+    /// Get the status of HWP dynamic boost, if it is available.
+    pub async fn hwp_dynamic_boost_async(&self) -> Result<Option<bool>, PStateError> {
+        match self.hwp_boost_attr_async().await? {
+            Some(attr) => self.get_async(attr).await.map(|Bool01(boost)| Some(boost)),
+            None => Ok(None),
+        }
+    }
+
+    /// Set the HWP dynamic boost status.
+    pub async fn set_hwp_dynamic_boost_async(&self, boost: bool) -> Result<(), PStateError> {
+        self.check_supported(HWP_DYNAMIC_BOOST)?;
+        let attr = self
+            .hwp_boost_attr_async()
+            .await?
+            .unwrap_or(HWP_DYNAMIC_BOOST);
+        self.set_async(attr, Bool01(boost)).await
+    }
+
+    /// Get the minimum performance percent.
+    pub async fn min_perf_pct_async(&self) -> Result<Percent, PStateError> {
+        self.check_supported(MIN_PERF_PCT)?;
+        self.get_async(MIN_PERF_PCT).await
+    }
+
+    /// Set the minimum performance percent.
+    pub async fn set_min_perf_pct_async(&self, min: Percent) -> Result<(), PStateError> {
+        self.check_supported(MIN_PERF_PCT)?;
+        self.check_policy(MIN_PERF_PCT, min)?;
+        self.set_async(MIN_PERF_PCT, min).await
+    }
+
+    /// Get the maximum performance percent.
+    pub async fn max_perf_pct_async(&self) -> Result<Percent, PStateError> {
+        self.check_supported(MAX_PERF_PCT)?;
+        self.get_async(MAX_PERF_PCT).await
+    }
+
+    /// Set the maximum performance percent.
+    pub async fn set_max_perf_pct_async(&self, max: Percent) -> Result<(), PStateError> {
+        self.check_supported(MAX_PERF_PCT)?;
+        self.check_policy(MAX_PERF_PCT, max)?;
+        self.set_async(MAX_PERF_PCT, max).await
+    }
+
+    /// If true, this signifies that turbo is disabled.
+    pub async fn no_turbo_async(&self) -> Result<bool, PStateError> {
+        self.check_supported(NO_TURBO)?;
+        let Bool01(no_turbo) = self.get_async(NO_TURBO).await?;
+        Ok(no_turbo)
+    }
+
+    /// Set the no_turbo value; `true` will disable turbo.
+    pub async fn set_no_turbo_async(&self, no_turbo: bool) -> Result<(), PStateError> {
+        self.check_supported(NO_TURBO)?;
+        self.set_async(NO_TURBO, Bool01(no_turbo)).await
+    }
+
+    /// Get current PState values.
+    pub async fn values_async(&self) -> Result<PStateValues, PStateError> {
+        let values = PStateValues {
+            min_perf_pct: self.min_perf_pct_async().await?,
+            max_perf_pct: self.max_perf_pct_async().await?,
+            no_turbo: self.no_turbo_async().await?,
+            hwp_dynamic_boost: self.hwp_dynamic_boost_async().await?,
+        };
+
+        Ok(values)
+    }
+
+    /// Set all values in the given config.
+    ///
+    /// This behaves the same as [`PState::set_values`].
+    ///
+    /// # Panics
+    ///
+    /// As with `set_values`, debug builds panic if `min_perf_pct` exceeds `max_perf_pct`.
+    pub async fn set_values_async(&self, values: PStateValues) -> Result<(), PStateError> {
+        debug_assert!(
+            values.min_perf_pct <= values.max_perf_pct,
+            "min_perf_pct ({}) is greater than max_perf_pct ({})",
+            values.min_perf_pct,
+            values.max_perf_pct
+        );
+
+        values.validate()?;
+        self.check_policy_range(Some(values.min_perf_pct), Some(values.max_perf_pct))?;
+
+        let boost = match values.hwp_dynamic_boost {
+            Some(boost) => self.set_hwp_dynamic_boost_async(boost).await,
+            None => Ok(()),
+        };
+
+        collect_errors(vec![
+            boost,
+            self.set_perf_range_async(values.min_perf_pct, values.max_perf_pct)
+                .await,
+            self.set_no_turbo_async(values.no_turbo).await,
+        ])
+    }
+
+    /// Set both performance percents in a valid order, as [`PState::set_perf_range`] does.
+    async fn set_perf_range_async(&self, min: Percent, max: Percent) -> Result<(), PStateError> {
+        if max >= self.min_perf_pct_async().await? {
+            self.set_max_perf_pct_async(max).await?;
+            self.set_min_perf_pct_async(min).await
+        } else {
+            self.set_min_perf_pct_async(min).await?;
+            self.set_max_perf_pct_async(max).await
+        }
+    }
+
+    /// Read and parse an attribute in the driver directory.
+    async fn get_async<F: FromStr>(&self, attr: &'static str) -> Result<F, PStateError>
+    where
+        F::Err: Display,
+    {
+        let path = self.file(attr);
+        self.parse_file_async(path.clone())
+            .await
+            .map_err(|source| PStateError::get_value(attr, path, source))
+    }
+
+    /// Write a value to an attribute in the driver directory.
+    async fn set_async<V: Display>(&self, attr: &'static str, value: V) -> Result<(), PStateError> {
+        let path = self.file(attr);
+        self.write_value_async(path.clone(), value)
+            .await
+            .map_err(|source| PStateError::set_value(attr, path, source))
+    }
+
+    async fn hwp_boost_attr_async(&self) -> Result<Option<&'static str>, PStateError> {
+        let pstate = self.clone();
+        spawn_blocking(move || pstate.hwp_boost_attr())
+            .await
+            .map_err(|why| {
+                let path = self.file(HWP_DYNAMIC_BOOST);
+                PStateError::get_value(HWP_DYNAMIC_BOOST, path, io::Error::other(why))
+            })
+    }
+
+    async fn parse_file_async<F: FromStr>(&self, path: PathBuf) -> io::Result<F>
+    where
+        F::Err: Display,
+    {
+        let backend = self.backend.clone();
+        let contents = spawn_blocking(move || read_traced(&*backend, &path))
+            .await
+            .map_err(io::Error::other)??;
+        parse_value(&contents)
+    }
+
+    /// Write a value that implements `Display` to a file
+    async fn write_value_async<V: Display>(&self, path: PathBuf, value: V) -> io::Result<()> {
+        let backend = self.backend.clone();
+        let on_mutation = self.on_mutation.clone();
+        let value = value.to_string();
+        spawn_blocking(move || write_traced(&*backend, on_mutation.as_ref(), &path, &value))
+            .await
+            .map_err(io::Error::other)?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::*;
+    use crate::*;
+
+    #[tokio::test]
+    async fn values_round_trip_asynchronously() {
+        let (pstate, backend) = mock(VALUES);
+        assert_eq!(
+            pstate.values_async().await.unwrap(),
+            pstate.values().unwrap()
+        );
+
+        let values = PStateValues::default()
+            .min_perf_pct(pct(30))
+            .max_perf_pct(pct(90))
+            .no_turbo(true)
+            .hwp_dynamic_boost(false);
+        pstate.set_values_async(values).await.unwrap();
+        assert_eq!(pstate.values_async().await.unwrap(), values);
+        assert_eq!(backend.writes().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn missing_hwp_boost_is_none_asynchronously() {
+        let (pstate, _) = mock(&[("intel_pstate/status", "active\n")]);
+        assert_eq!(pstate.hwp_dynamic_boost_async().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn set_values_async_writes_a_decreasing_range_minimum_first() {
+        let (pstate, backend) = mock(VALUES);
+        let values = pstate
+            .values()
+            .unwrap()
+            .min_perf_pct(pct(5))
+            .max_perf_pct(pct(10));
+
+        pstate.set_values_async(values).await.unwrap();
+        assert_eq!(
+            writes(&backend)[1..3],
+            [
+                ("intel_pstate/min_perf_pct".to_owned(), "5".to_owned()),
+                ("intel_pstate/max_perf_pct".to_owned(), "10".to_owned()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn set_values_async_checks_the_policy_before_any_write() {
+        let (pstate, backend) = mock(VALUES);
+        pstate.set_policy(PStatePolicy::default().max_allowed_min(pct(10)));
+
+        let values = pstate
+            .values()
+            .unwrap()
+            .hwp_dynamic_boost(false)
+            .min_perf_pct(pct(30));
+        assert!(matches!(
+            pstate.set_values_async(values).await,
+            Err(PStateError::PolicyViolation {
+                src: MIN_PERF_PCT,
+                ..
+            })
+        ));
+        assert!(backend.writes().is_empty());
+    }
+
+    #[cfg(debug_assertions)]
+    #[tokio::test]
+    #[should_panic(expected = "min_perf_pct (60) is greater than max_perf_pct (40)")]
+    async fn set_values_async_panics_on_an_inverted_range_in_debug_builds() {
+        let (pstate, _) = mock(VALUES);
+        let values = PStateValues::default()
+            .min_perf_pct(pct(60))
+            .max_perf_pct(pct(40));
+        let _ = pstate.set_values_async(values).await;
+    }
+
+    #[cfg(not(debug_assertions))]
+    #[tokio::test]
+    async fn set_values_async_rejects_an_inverted_range_without_writing() {
+        let (pstate, backend) = mock(VALUES);
+        let values = PStateValues::default()
+            .min_perf_pct(pct(60))
+            .max_perf_pct(pct(40));
+
+        assert!(matches!(
+            pstate.set_values_async(values).await,
+            Err(PStateError::InvalidRange { .. })
+        ));
+        assert!(backend.writes().is_empty());
+    }
+}
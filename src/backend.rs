@@ -0,0 +1,399 @@
+// Copyright 2022 System76 <info@system76.com>
+// SPDX-License-Identifier: MIT
+
+//! Abstraction over the filesystem that sysfs attributes are read from and written to.
+
+#[cfg(any(test, feature = "test-util"))]
+use std::{
+    collections::{HashMap, VecDeque},
+    path::PathBuf,
+    sync::MutexGuard,
+};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+/// Provides access to the files which a `PState` handle reads and writes.
+///
+/// Paths given to a backend are derived from the directory the handle was created
+/// with. Driver attributes are joined onto that directory, and per-CPU attributes onto
+/// its parent, such as `/sys/devices/system/cpu/cpu0/cpufreq/`. The hybrid CPU lists
+/// are found two levels above it, in `cpu_core/` and `cpu_atom/`. Power supplies are
+/// the exception, and are always read from the absolute `/sys/class/power_supply/`.
+pub trait PStateBackend: Send + Sync {
+    /// Read the entire contents of a file.
+    fn read(&self, path: &Path) -> io::Result<String>;
+
+    /// Replace the contents of a file.
+    fn write(&self, path: &Path, value: &str) -> io::Result<()>;
+
+    /// Whether a file or directory exists.
+    fn exists(&self, path: &Path) -> bool;
+
+    /// Whether a directory exists.
+    fn is_dir(&self, path: &Path) -> bool;
+
+    /// List the names of the regular files in a directory, excluding directories and symlinks.
+    fn list_files(&self, _dir: &Path) -> io::Result<Vec<String>> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    /// List the names of the subdirectories of a directory, including symlinks to directories.
+    fn list_dirs(&self, _dir: &Path) -> io::Result<Vec<String>> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    /// Whether an existing file may be written to.
+    fn can_write(&self, path: &Path) -> bool {
+        self.exists(path)
+    }
+}
+
+/// The default backend, which accesses the real filesystem.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SysfsBackend;
+
+impl PStateBackend for SysfsBackend {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+
+    fn write(&self, path: &Path, value: &str) -> io::Result<()> {
+        write!(File::create(path)?, "{}", value)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        path.is_dir()
+    }
+
+    fn list_files(&self, dir: &Path) -> io::Result<Vec<String>> {
+        let mut files = Vec::new();
+
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                files.push(entry.file_name().to_string_lossy().into_owned());
+            }
+        }
+
+        Ok(files)
+    }
+
+    fn list_dirs(&self, dir: &Path) -> io::Result<Vec<String>> {
+        let mut dirs = Vec::new();
+
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            if entry.path().is_dir() {
+                dirs.push(entry.file_name().to_string_lossy().into_owned());
+            }
+        }
+
+        Ok(dirs)
+    }
+
+    fn can_write(&self, path: &Path) -> bool {
+        OpenOptions::new().append(true).open(path).is_ok()
+    }
+}
+
+/// Reads through another backend, but records writes instead of performing them.
+pub(crate) struct DryRunBackend {
+    pub(crate) inner: Arc<dyn PStateBackend>,
+    pub(crate) writes: Arc<Mutex<Vec<(String, String)>>>,
+}
+
+impl PStateBackend for DryRunBackend {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        self.inner.read(path)
+    }
+
+    fn write(&self, path: &Path, value: &str) -> io::Result<()> {
+        if !self.inner.exists(path) {
+            return Err(io::ErrorKind::NotFound.into());
+        }
+
+        self.writes
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .push((path.display().to_string(), value.to_owned()));
+        Ok(())
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.inner.exists(path)
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.inner.is_dir(path)
+    }
+
+    fn list_files(&self, dir: &Path) -> io::Result<Vec<String>> {
+        self.inner.list_files(dir)
+    }
+
+    fn list_dirs(&self, dir: &Path) -> io::Result<Vec<String>> {
+        self.inner.list_dirs(dir)
+    }
+
+    fn can_write(&self, path: &Path) -> bool {
+        self.exists(path)
+    }
+}
+
+/// An in-memory backend for tests, which records every read and write.
+///
+/// Clones share the same files and logs, so a clone may be kept to inspect the
+/// backend after giving it to a `PState`. As with sysfs, files cannot be created
+/// by writing to them; they must be added beforehand with [`MemoryBackend::set`].
+#[cfg(any(test, feature = "test-util"))]
+#[derive(Clone, Debug, Default)]
+pub struct MemoryBackend {
+    state: Arc<Mutex<MemoryState>>,
+}
+
+#[cfg(any(test, feature = "test-util"))]
+#[derive(Debug, Default)]
+struct MemoryState {
+    files: HashMap<PathBuf, String>,
+    write_errors: HashMap<PathBuf, VecDeque<io::ErrorKind>>,
+    reads: Vec<PathBuf>,
+    writes: Vec<(PathBuf, String)>,
+}
+
+#[cfg(any(test, feature = "test-util"))]
+impl MemoryBackend {
+    /// Create a backend without any files.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a file, builder-style.
+    pub fn with_file<P: Into<PathBuf>, V: Into<String>>(self, path: P, value: V) -> Self {
+        self.set(path, value);
+        self
+    }
+
+    /// Add a file, or replace its contents, without recording a write.
+    pub fn set<P: Into<PathBuf>, V: Into<String>>(&self, path: P, value: V) {
+        self.state().files.insert(path.into(), value.into());
+    }
+
+    /// Get the contents of a file, without recording a read.
+    pub fn get<P: AsRef<Path>>(&self, path: P) -> Option<String> {
+        self.state().files.get(path.as_ref()).cloned()
+    }
+
+    /// Remove a file, returning its contents, without recording a write.
+    pub fn remove<P: AsRef<Path>>(&self, path: P) -> Option<String> {
+        self.state().files.remove(path.as_ref())
+    }
+
+    /// Make the next write to a file fail with the given kind of error.
+    ///
+    /// Calling this several times queues several failures.
+    pub fn fail_write<P: Into<PathBuf>>(&self, path: P, kind: io::ErrorKind) {
+        self.state()
+            .write_errors
+            .entry(path.into())
+            .or_default()
+            .push_back(kind);
+    }
+
+    /// The files that have been read, in order.
+    pub fn reads(&self) -> Vec<PathBuf> {
+        self.state().reads.clone()
+    }
+
+    /// The values that have been written, in order.
+    pub fn writes(&self) -> Vec<(PathBuf, String)> {
+        self.state().writes.clone()
+    }
+
+    /// Forget the reads and writes recorded so far.
+    pub fn clear_log(&self) {
+        let mut state = self.state();
+        state.reads.clear();
+        state.writes.clear();
+    }
+
+    fn state(&self) -> MutexGuard<'_, MemoryState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(any(test, feature = "test-util"))]
+impl PStateBackend for MemoryBackend {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        let mut state = self.state();
+        state.reads.push(path.to_owned());
+        state
+            .files
+            .get(path)
+            .cloned()
+            .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
+    }
+
+    fn write(&self, path: &Path, value: &str) -> io::Result<()> {
+        let mut state = self.state();
+
+        if let Some(kind) = state
+            .write_errors
+            .get_mut(path)
+            .and_then(VecDeque::pop_front)
+        {
+            return Err(io::Error::from(kind));
+        }
+
+        match state.files.get_mut(path) {
+            Some(contents) => *contents = value.to_owned(),
+            None => return Err(io::Error::from(io::ErrorKind::NotFound)),
+        }
+
+        state.writes.push((path.to_owned(), value.to_owned()));
+        Ok(())
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        let is_file = self.state().files.contains_key(path);
+        is_file || self.is_dir(path)
+    }
+
+    fn list_files(&self, dir: &Path) -> io::Result<Vec<String>> {
+        if !self.is_dir(dir) {
+            return Err(io::ErrorKind::NotFound.into());
+        }
+
+        Ok(self
+            .state()
+            .files
+            .keys()
+            .filter(|file| file.parent() == Some(dir))
+            .filter_map(|file| file.file_name())
+            .map(|name| name.to_string_lossy().into_owned())
+            .collect())
+    }
+
+    fn list_dirs(&self, dir: &Path) -> io::Result<Vec<String>> {
+        if !self.is_dir(dir) {
+            return Err(io::ErrorKind::NotFound.into());
+        }
+
+        let mut dirs: Vec<String> = self
+            .state()
+            .files
+            .keys()
+            .filter_map(|file| file.strip_prefix(dir).ok())
+            .filter(|relative| relative.components().count() > 1)
+            .filter_map(|relative| relative.iter().next())
+            .map(|name| name.to_string_lossy().into_owned())
+            .collect();
+
+        dirs.sort_unstable();
+        dirs.dedup();
+        Ok(dirs)
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.state()
+            .files
+            .keys()
+            .any(|file| file != path && file.starts_with(path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{tests::*, PState, PStateValuesDiff};
+
+    /// A backend which exposes a single directory holding the same value in every file.
+    struct ConstBackend;
+
+    impl PStateBackend for ConstBackend {
+        fn read(&self, _path: &Path) -> io::Result<String> {
+            Ok("42\n".to_owned())
+        }
+
+        fn write(&self, _path: &Path, _value: &str) -> io::Result<()> {
+            Err(io::ErrorKind::PermissionDenied.into())
+        }
+
+        fn exists(&self, _path: &Path) -> bool {
+            true
+        }
+
+        fn is_dir(&self, path: &Path) -> bool {
+            path.ends_with("intel_pstate")
+        }
+    }
+
+    #[test]
+    fn custom_backends_serve_every_attribute() {
+        let pstate = PState::with_backend("intel_pstate", ConstBackend).unwrap();
+
+        assert_eq!(pstate.max_perf_pct().unwrap(), pct(42));
+        assert_eq!(pstate.scaling_cur_freq(3).unwrap(), 42);
+        assert!(pstate.set_min_perf_pct(pct(10)).is_err());
+        assert!(PState::with_backend("amd_pstate", ConstBackend).is_err());
+    }
+
+    #[test]
+    fn memory_backend_logs_reads_and_writes_in_order() {
+        let backend = MemoryBackend::new()
+            .with_file("/a", "1")
+            .with_file("/b", "2");
+
+        assert_eq!(backend.read(Path::new("/a")).unwrap(), "1");
+        backend.write(Path::new("/b"), "3").unwrap();
+        backend.fail_write("/a", io::ErrorKind::ResourceBusy);
+        assert_eq!(
+            backend.write(Path::new("/a"), "4").unwrap_err().kind(),
+            io::ErrorKind::ResourceBusy
+        );
+        backend.write(Path::new("/a"), "5").unwrap();
+        assert_eq!(
+            backend.write(Path::new("/c"), "6").unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+
+        assert_eq!(backend.reads(), [PathBuf::from("/a")]);
+        assert_eq!(
+            backend.writes(),
+            [
+                (PathBuf::from("/b"), "3".to_owned()),
+                (PathBuf::from("/a"), "5".to_owned()),
+            ]
+        );
+
+        backend.clear_log();
+        assert!(backend.reads().is_empty() && backend.writes().is_empty());
+        assert_eq!(backend.get("/a").as_deref(), Some("5"));
+    }
+
+    #[test]
+    fn memory_backend_records_a_decreasing_range_and_an_empty_diff() {
+        let (pstate, backend) = mock(VALUES);
+
+        pstate.apply_diff(&PStateValuesDiff::default()).unwrap();
+        assert!(backend.writes().is_empty());
+
+        pstate.set_perf_range(pct(5), pct(10)).unwrap();
+        assert_eq!(
+            backend.writes(),
+            [
+                (path("intel_pstate/min_perf_pct"), "5".to_owned()),
+                (path("intel_pstate/max_perf_pct"), "10".to_owned()),
+            ]
+        );
+    }
+}
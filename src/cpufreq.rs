@@ -0,0 +1,1603 @@
+// Copyright 2022 System76 <info@system76.com>
+// SPDX-License-Identifier: MIT
+
+//! Per-CPU attributes, most of which are found in `/sys/devices/system/cpu/cpuN/cpufreq/`.
+
+use crate::*;
+
+use std::time::Instant;
+
+const BASE_FREQUENCY: &str = "base_frequency";
+const CORE_THROTTLE_COUNT: &str = "core_throttle_count";
+const CPUINFO_MAX_FREQ: &str = "cpuinfo_max_freq";
+const CPUINFO_MIN_FREQ: &str = "cpuinfo_min_freq";
+const ENERGY_PERF_BIAS: &str = "energy_perf_bias";
+const ENERGY_PERFORMANCE_AVAILABLE_PREFERENCES: &str = "energy_performance_available_preferences";
+const ENERGY_PERFORMANCE_PREFERENCE: &str = "energy_performance_preference";
+const ONLINE: &str = "online";
+const PRESENT: &str = "present";
+const SCALING_CUR_FREQ: &str = "scaling_cur_freq";
+const SCALING_DRIVER: &str = "scaling_driver";
+const SCALING_AVAILABLE_FREQUENCIES: &str = "scaling_available_frequencies";
+const SCALING_AVAILABLE_GOVERNORS: &str = "scaling_available_governors";
+const SCALING_GOVERNOR: &str = "scaling_governor";
+const SCALING_MAX_FREQ: &str = "scaling_max_freq";
+const SCALING_MIN_FREQ: &str = "scaling_min_freq";
+
+/// How often [`PState::wait_until_max_below`] reads the current frequencies.
+const SETTLE_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// An energy performance preference (EPP), which hints to the hardware whether
+/// to favor performance or power savings.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Epp {
+    /// `default`: the preference set by the firmware.
+    Default,
+    /// `performance`
+    Performance,
+    /// `balance_performance`
+    BalancePerformance,
+    /// `balance_power`
+    BalancePower,
+    /// `power`
+    Power,
+    /// Any other preference, such as a firmware-specific name or a raw value from 0 to 255.
+    Raw(String),
+}
+
+impl FromStr for Epp {
+    type Err = std::convert::Infallible;
+
+    /// Parse a kernel preference string. Unknown preferences become `Epp::Raw`.
+    fn from_str(s: &str) -> Result<Epp, Self::Err> {
+        Ok(match s {
+            "default" => Epp::Default,
+            "performance" => Epp::Performance,
+            "balance_performance" => Epp::BalancePerformance,
+            "balance_power" => Epp::BalancePower,
+            "power" => Epp::Power,
+            _ => Epp::Raw(s.to_owned()),
+        })
+    }
+}
+
+impl Display for Epp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Epp::Default => "default",
+            Epp::Performance => "performance",
+            Epp::BalancePerformance => "balance_performance",
+            Epp::BalancePower => "balance_power",
+            Epp::Power => "power",
+            Epp::Raw(preference) => preference,
+        })
+    }
+}
+
+/// The current frequencies of the online CPUs, summarized.
+#[derive(Copy, Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct FreqSummary {
+    pub min_khz: u64,
+    pub max_khz: u64,
+    pub avg_khz: u64,
+}
+
+/// A performance limit, given either as a percent of the driver's range, or as a
+/// frequency.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub enum PerfBound {
+    /// A percent, which is written to the driver's global `min_perf_pct` or `max_perf_pct`.
+    Percent(Percent),
+    /// A frequency in kHz, which is written to `scaling_min_freq` or `scaling_max_freq`
+    /// of every online CPU.
+    Khz(u64),
+}
+
+/// The type of a core on a hybrid CPU, as returned by [`PState::core_types`].
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub enum CoreType {
+    /// A performance core (P-core). Every core of a non-hybrid CPU is of this type.
+    Performance,
+    /// An efficiency core (E-core).
+    Efficiency,
+}
+
+/// The cpufreq state of a single online CPU, as returned by [`PState::per_cpu_values`].
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct CpuState {
+    pub cpu: usize,
+    pub cur_freq_khz: u64,
+    pub min_freq_khz: u64,
+    pub max_freq_khz: u64,
+    pub governor: String,
+    /// The energy performance preference, which is only available when HWP is enabled.
+    pub epp: Option<Epp>,
+}
+
+/// The HWP controls of a single CPU, as returned by [`PState::hwp_request`].
+///
+/// The kernel converts the frequency limits into the CPU's HWP request, along
+/// with the energy performance preference.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct HwpRequest {
+    /// The lowest frequency the hardware may select, in kHz.
+    pub min_freq: u64,
+    /// The highest frequency the hardware may select, in kHz.
+    pub max_freq: u64,
+    pub epp: Epp,
+}
+
+impl PState {
+    /// Whether hardware-managed P-states (HWP) are in use.
+    ///
+    /// This is detected by the presence of `energy_performance_preference` in CPU0's
+    /// cpufreq directory, which the driver only creates when HWP is enabled.
+    pub fn hwp_active(&self) -> bool {
+        self.backend
+            .exists(&self.cpufreq_file(0, ENERGY_PERFORMANCE_PREFERENCE))
+    }
+
+    /// Get the energy performance preference (EPP) of a CPU, such as `balance_performance`.
+    ///
+    /// This is only available when HWP is enabled.
+    pub fn epp(&self, cpu: usize) -> Result<Epp, PStateError> {
+        self.cpufreq_value(cpu, ENERGY_PERFORMANCE_PREFERENCE)
+    }
+
+    /// Get CPU0's energy performance preference (EPP) as a representative value for
+    /// the whole system, or `None` if HWP is not enabled.
+    ///
+    /// This assumes that every CPU has the same preference, as is the case after
+    /// [`PState::set_epp_all`]. Use [`PState::epp`] to read each CPU when that may
+    /// not hold.
+    pub fn global_epp(&self) -> Result<Option<Epp>, PStateError> {
+        if !self.hwp_active() {
+            return Ok(None);
+        }
+
+        self.epp(0).map(Some)
+    }
+
+    /// Get the energy performance preferences supported by a CPU.
+    pub fn available_epp(&self, cpu: usize) -> Result<Vec<Epp>, PStateError> {
+        self.parse_list(&self.cpufreq_file(cpu, ENERGY_PERFORMANCE_AVAILABLE_PREFERENCES))
+            .map(|preferences| {
+                preferences
+                    .iter()
+                    .filter_map(|preference| preference.parse().ok())
+                    .collect()
+            })
+            .map_err(|source| {
+                PStateError::get_cpu_value(
+                    ENERGY_PERFORMANCE_AVAILABLE_PREFERENCES,
+                    cpu,
+                    self.cpufreq_file(cpu, ENERGY_PERFORMANCE_AVAILABLE_PREFERENCES),
+                    source,
+                )
+            })
+    }
+
+    /// Set the energy performance preference (EPP) of a CPU.
+    ///
+    /// An `Epp::Raw` preference is written as it is, and must not be empty or
+    /// contain whitespace.
+    pub fn set_epp(&self, cpu: usize, preference: &Epp) -> Result<(), PStateError> {
+        if let Epp::Raw(raw) = preference {
+            if raw.is_empty() || raw.contains(char::is_whitespace) {
+                return Err(PStateError::InvalidValue {
+                    src: ENERGY_PERFORMANCE_PREFERENCE,
+                    value: raw.clone(),
+                });
+            }
+        }
+
+        self.set_cpufreq_value(cpu, ENERGY_PERFORMANCE_PREFERENCE, preference)
+    }
+
+    /// Set the energy performance preference (EPP) of every online CPU.
+    pub fn set_epp_all(&self, preference: &Epp) -> Result<(), PStateError> {
+        for cpu in self.online_cpus()? {
+            self.set_epp(cpu, preference)?;
+        }
+
+        Ok(())
+    }
+
+    /// Get the HWP frequency limits and energy performance preference of a CPU.
+    ///
+    /// This is only available when HWP is enabled.
+    pub fn hwp_request(&self, cpu: usize) -> Result<HwpRequest, PStateError> {
+        Ok(HwpRequest {
+            min_freq: self.scaling_min_freq(cpu)?,
+            max_freq: self.scaling_max_freq(cpu)?,
+            epp: self.epp(cpu)?,
+        })
+    }
+
+    /// Set the HWP frequency limits and energy performance preference of a CPU.
+    ///
+    /// Both frequencies must be within the hardware limits of the CPU, and the
+    /// minimum must not exceed the maximum, or `PStateError::OutOfRange` is returned
+    /// without writing anything. The limits are written in whichever order keeps
+    /// the minimum below the maximum throughout.
+    pub fn set_hwp_request(&self, cpu: usize, request: &HwpRequest) -> Result<(), PStateError> {
+        let (min, max) = self.cpuinfo_freq_cached(cpu)?;
+
+        for &(src, khz) in &[
+            (SCALING_MIN_FREQ, request.min_freq),
+            (SCALING_MAX_FREQ, request.max_freq),
+        ] {
+            if khz < min || khz > max {
+                return Err(PStateError::OutOfRange { src, value: khz });
+            }
+        }
+
+        if request.min_freq > request.max_freq {
+            return Err(PStateError::OutOfRange {
+                src: SCALING_MIN_FREQ,
+                value: request.min_freq,
+            });
+        }
+
+        if request.min_freq > self.scaling_max_freq(cpu)? {
+            self.set_cpufreq_value(cpu, SCALING_MAX_FREQ, request.max_freq)?;
+            self.set_cpufreq_value(cpu, SCALING_MIN_FREQ, request.min_freq)?;
+        } else {
+            self.set_cpufreq_value(cpu, SCALING_MIN_FREQ, request.min_freq)?;
+            self.set_cpufreq_value(cpu, SCALING_MAX_FREQ, request.max_freq)?;
+        }
+
+        self.set_epp(cpu, &request.epp)
+    }
+
+    /// Get the name of the scaling driver in use by a CPU, such as `intel_pstate`,
+    /// `intel_cpufreq`, `amd-pstate`, or `acpi-cpufreq`.
+    ///
+    /// In passive mode, intel_pstate registers itself as `intel_cpufreq`.
+    pub fn scaling_driver(&self, cpu: usize) -> Result<String, PStateError> {
+        self.cpufreq_value(cpu, SCALING_DRIVER)
+    }
+
+    /// Get the cpufreq governor of a CPU.
+    ///
+    /// Governors are only used when the driver is in passive mode.
+    pub fn scaling_governor(&self, cpu: usize) -> Result<String, PStateError> {
+        self.cpufreq_value(cpu, SCALING_GOVERNOR)
+    }
+
+    /// Get the cpufreq governors which a CPU may use.
+    ///
+    /// This fails if the cpufreq directory is missing, which is the case when the
+    /// driver does not use governors.
+    pub fn available_governors(&self, cpu: usize) -> Result<Vec<String>, PStateError> {
+        self.parse_list(&self.cpufreq_file(cpu, SCALING_AVAILABLE_GOVERNORS))
+            .map_err(|source| {
+                PStateError::get_cpu_value(
+                    SCALING_AVAILABLE_GOVERNORS,
+                    cpu,
+                    self.cpufreq_file(cpu, SCALING_AVAILABLE_GOVERNORS),
+                    source,
+                )
+            })
+    }
+
+    /// Set the cpufreq governor of a CPU, such as `powersave` or `performance`.
+    ///
+    /// The governor must be listed in the CPU's `scaling_available_governors`.
+    pub fn set_scaling_governor(&self, cpu: usize, governor: &str) -> Result<(), PStateError> {
+        let available = self.available_governors(cpu)?;
+
+        if !available.iter().any(|available| available == governor) {
+            return Err(PStateError::InvalidValue {
+                src: SCALING_GOVERNOR,
+                value: governor.to_owned(),
+            });
+        }
+
+        self.set_cpufreq_value(cpu, SCALING_GOVERNOR, governor)
+    }
+
+    /// Set the cpufreq governor of every online CPU.
+    pub fn set_scaling_governor_all(&self, governor: &str) -> Result<(), PStateError> {
+        for cpu in self.online_cpus()? {
+            self.set_scaling_governor(cpu, governor)?;
+        }
+
+        Ok(())
+    }
+
+    /// Get the current frequency of a CPU in kHz.
+    ///
+    /// If the CPU is offline or lacks the file, `PStateError::Missing` is returned;
+    /// if the contents are malformed, `PStateError::Parse` is returned.
+    pub fn scaling_cur_freq(&self, cpu: usize) -> Result<u64, PStateError> {
+        self.cpufreq_value(cpu, SCALING_CUR_FREQ)
+    }
+
+    /// Get the current frequency of a CPU in MHz.
+    pub fn scaling_cur_freq_mhz(&self, cpu: usize) -> Result<u64, PStateError> {
+        self.scaling_cur_freq(cpu).map(|khz| khz / 1000)
+    }
+
+    /// Summarize the current frequencies of every online CPU.
+    pub fn frequency_summary(&self) -> Result<FreqSummary, PStateError> {
+        let mut freqs = Vec::new();
+
+        for cpu in self.online_cpus()? {
+            freqs.push(self.scaling_cur_freq(cpu)?);
+        }
+
+        if freqs.is_empty() {
+            return Ok(FreqSummary::default());
+        }
+
+        Ok(FreqSummary {
+            min_khz: freqs.iter().copied().min().unwrap_or(0),
+            max_khz: freqs.iter().copied().max().unwrap_or(0),
+            avg_khz: freqs.iter().sum::<u64>() / freqs.len() as u64,
+        })
+    }
+
+    /// Summarize the current frequencies of every online CPU once per `interval`, over
+    /// `duration`, such as while a workload runs.
+    ///
+    /// Samples are scheduled relative to the first, so the time spent reading does not
+    /// accumulate as drift. At least one sample is taken. If a read fails, such as when
+    /// a CPU goes offline partway through, sampling stops at once and the error, which
+    /// names the file of the CPU that failed, is returned.
+    pub fn sample_frequencies(
+        &self,
+        duration: Duration,
+        interval: Duration,
+    ) -> Result<Vec<FreqSummary>, PStateError> {
+        self.sample_frequencies_with(duration, interval, Instant::now, thread::sleep)
+    }
+
+    /// [`PState::sample_frequencies`], with the clock and sleep supplied by the caller.
+    fn sample_frequencies_with(
+        &self,
+        duration: Duration,
+        interval: Duration,
+        mut now: impl FnMut() -> Instant,
+        mut sleep: impl FnMut(Duration),
+    ) -> Result<Vec<FreqSummary>, PStateError> {
+        if interval.is_zero() {
+            return Err(PStateError::OutOfRange {
+                src: "sample interval",
+                value: 0,
+            });
+        }
+
+        let count = (duration.as_nanos() / interval.as_nanos()).max(1);
+        let mut deadline = now();
+        let mut samples = Vec::new();
+
+        for _ in 0..count {
+            sleep(deadline.saturating_duration_since(now()));
+            deadline += interval;
+
+            samples.push(self.frequency_summary()?);
+        }
+
+        Ok(samples)
+    }
+
+    /// Block until every online CPU is running below `khz`, or until `timeout` elapses.
+    ///
+    /// Returns whether the frequencies settled in time. Clocks take a moment to follow
+    /// a new limit, so this is useful to confirm that a lower `max_perf_pct` has taken
+    /// effect before measuring. The frequencies are checked at least once, even with
+    /// a zero timeout.
+    pub fn wait_until_max_below(&self, khz: u64, timeout: Duration) -> Result<bool, PStateError> {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            if self.frequency_summary()?.max_khz < khz {
+                return Ok(true);
+            }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Ok(false);
+            }
+
+            thread::sleep(remaining.min(SETTLE_POLL_INTERVAL));
+        }
+    }
+
+    /// Get the minimum frequency supported by a CPU, in kHz.
+    pub fn cpuinfo_min_freq(&self, cpu: usize) -> Result<u64, PStateError> {
+        self.cpufreq_value(cpu, CPUINFO_MIN_FREQ)
+    }
+
+    /// Get the maximum frequency supported by a CPU, in kHz.
+    pub fn cpuinfo_max_freq(&self, cpu: usize) -> Result<u64, PStateError> {
+        self.cpufreq_value(cpu, CPUINFO_MAX_FREQ)
+    }
+
+    /// Get the minimum and maximum frequencies supported by a CPU, in kHz.
+    ///
+    /// These do not change while the system is running, so they are only read
+    /// once per CPU and are cached by this handle.
+    pub fn cpuinfo_freq_cached(&self, cpu: usize) -> Result<(u64, u64), PStateError> {
+        let mut cache = self
+            .cpuinfo_freq
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        if let Some(&limits) = cache.get(&cpu) {
+            return Ok(limits);
+        }
+
+        let limits = (self.cpuinfo_min_freq(cpu)?, self.cpuinfo_max_freq(cpu)?);
+        cache.insert(cpu, limits);
+        Ok(limits)
+    }
+
+    /// Get the lowest minimum and highest maximum frequencies supported by any online
+    /// CPU, in kHz, such as for the bounds of a single system-wide slider.
+    ///
+    /// On hybrid CPUs, the limits of the performance and efficiency cores differ, so
+    /// the range spans both. `(0, 0)` is returned if no CPU is online.
+    pub fn global_freq_range(&self) -> Result<(u64, u64), PStateError> {
+        let mut range: Option<(u64, u64)> = None;
+
+        for cpu in self.online_cpus()? {
+            let (min, max) = self.cpuinfo_freq_cached(cpu)?;
+            range = Some(match range {
+                Some((lowest, highest)) => (lowest.min(min), highest.max(max)),
+                None => (min, max),
+            });
+        }
+
+        Ok(range.unwrap_or_default())
+    }
+
+    /// Get the type of each online CPU as pairs of CPU index and type, ordered by CPU
+    /// index, to tell performance cores from efficiency cores on hybrid CPUs such as
+    /// Alder Lake. Offline CPUs are omitted.
+    ///
+    /// On kernels which register separate `cpu_core` and `cpu_atom` PMUs in
+    /// `/sys/devices/`, the CPUs listed by `cpu_atom` are efficiency cores. Otherwise,
+    /// the cores are told apart by frequency: CPUs with the highest base frequency are
+    /// performance cores, and the rest are efficiency cores. If any CPU lacks a base
+    /// frequency, the maximum frequency is compared instead, which may mistake the
+    /// slower performance cores of a CPU with Turbo Boost Max 3.0 for efficiency cores.
+    pub fn core_types(&self) -> Result<Vec<(usize, CoreType)>, PStateError> {
+        let cpus = self.online_cpus()?;
+
+        if let Some(atom) = self.atom_cpus()? {
+            return Ok(cpus
+                .into_iter()
+                .map(|cpu| {
+                    if atom.contains(&cpu) {
+                        (cpu, CoreType::Efficiency)
+                    } else {
+                        (cpu, CoreType::Performance)
+                    }
+                })
+                .collect());
+        }
+
+        let base: Option<Vec<u64>> = cpus
+            .iter()
+            .map(|&cpu| self.base_frequency(cpu))
+            .collect::<Result<_, _>>()?;
+
+        let freqs = match base {
+            Some(base) => base,
+            None => cpus
+                .iter()
+                .map(|&cpu| self.cpuinfo_freq_cached(cpu).map(|(_, max)| max))
+                .collect::<Result<_, _>>()?,
+        };
+
+        let highest = freqs.iter().copied().max().unwrap_or(0);
+
+        Ok(cpus
+            .into_iter()
+            .zip(freqs)
+            .map(|(cpu, freq)| {
+                if freq == highest {
+                    (cpu, CoreType::Performance)
+                } else {
+                    (cpu, CoreType::Efficiency)
+                }
+            })
+            .collect())
+    }
+
+    /// The CPUs listed by the `cpu_atom` PMU, if both hybrid PMUs are registered.
+    fn atom_cpus(&self) -> Result<Option<Vec<usize>>, PStateError> {
+        let devices = match self.cpu_root().parent().and_then(Path::parent) {
+            Some(devices) => devices,
+            None => return Ok(None),
+        };
+
+        let core = devices.join("cpu_core").join("cpus");
+        let atom = devices.join("cpu_atom").join("cpus");
+
+        if !self.backend.exists(&core) || !self.backend.exists(&atom) {
+            return Ok(None);
+        }
+
+        self.read(&atom)
+            .and_then(|list| parse_cpu_list(&list))
+            .map(Some)
+            .map_err(|source| PStateError::get_value("cpu_atom", atom, source))
+    }
+
+    /// Get the guaranteed sustainable frequency of a CPU in kHz, if it is available.
+    ///
+    /// Frequencies above this are in turbo territory. Only systems with HWP report it.
+    pub fn base_frequency(&self, cpu: usize) -> Result<Option<u64>, PStateError> {
+        if self.backend.exists(&self.cpufreq_file(cpu, BASE_FREQUENCY)) {
+            return self.cpufreq_value(cpu, BASE_FREQUENCY).map(Some);
+        }
+
+        Ok(None)
+    }
+
+    /// Whether any online CPU is currently running above its base frequency.
+    ///
+    /// Unlike `no_turbo`, which only says whether turbo is permitted, this says whether
+    /// it is in use. CPUs which do not report a base frequency are skipped.
+    pub fn is_boosting(&self) -> Result<bool, PStateError> {
+        for cpu in self.online_cpus()? {
+            if let Some(base) = self.base_frequency(cpu)? {
+                if self.scaling_cur_freq(cpu)? > base {
+                    return Ok(true);
+                }
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Approximate the frequency of a CPU, in kHz, at the given performance percent.
+    ///
+    /// This interpolates linearly between the CPU's hardware limits, whereas the
+    /// driver's percent scale is not exactly linear with frequency, so the result
+    /// is only an estimate.
+    pub fn perf_pct_to_khz(&self, cpu: usize, pct: Percent) -> Result<u64, PStateError> {
+        let (min, max) = self.cpuinfo_freq_cached(cpu)?;
+        Ok(min + (max.saturating_sub(min) * u64::from(pct.get()) + 50) / 100)
+    }
+
+    /// Estimate the highest frequency that CPU0 may currently reach, in MHz, such as
+    /// to show that the CPU is capped at 2400 MHz.
+    ///
+    /// The ceiling is derived from `max_perf_pct` with [`PState::perf_pct_to_khz`],
+    /// so it is only as accurate as that estimate. When turbo is disabled, it is
+    /// further capped at the base frequency, if the system reports one. Limits
+    /// imposed by the hardware or firmware, such as thermal throttling, are not
+    /// taken into account.
+    pub fn effective_max_mhz(&self) -> Result<u64, PStateError> {
+        let mut khz = self.perf_pct_to_khz(0, self.max_perf_pct()?)?;
+
+        if self.no_turbo()? {
+            if let Some(base) = self.base_frequency(0)? {
+                khz = khz.min(base);
+            }
+        }
+
+        Ok(khz / 1000)
+    }
+
+    /// Approximate the performance percent of a CPU at the given frequency in kHz,
+    /// rounded to the nearest percent.
+    ///
+    /// Frequencies outside of the hardware limits saturate at 0 or 100. As with
+    /// [`PState::perf_pct_to_khz`], the result is only an estimate.
+    pub fn khz_to_perf_pct(&self, cpu: usize, khz: u64) -> Result<Percent, PStateError> {
+        let (min, max) = self.cpuinfo_freq_cached(cpu)?;
+        let span = max.saturating_sub(min);
+        let khz = khz.max(min).min(max);
+
+        if span == 0 {
+            return Ok(Percent::MAX);
+        }
+
+        Ok(Percent((((khz - min) * 100 + span / 2) / span) as u8))
+    }
+
+    /// Get the discrete frequencies which may be selected for a CPU, in kHz and sorted
+    /// in ascending order, if the driver lists them.
+    ///
+    /// intel_pstate does not list them, so this is usually `None` unless another
+    /// driver, such as acpi-cpufreq, is in use.
+    pub fn available_frequencies(&self, cpu: usize) -> Result<Option<Vec<u64>>, PStateError> {
+        let path = self.cpufreq_file(cpu, SCALING_AVAILABLE_FREQUENCIES);
+
+        if !self.backend.exists(&path) {
+            return Ok(None);
+        }
+
+        let mut frequencies: Vec<u64> = self
+            .parse_list(&path)
+            .and_then(|list| list.iter().map(|freq| parse_value(freq)).collect())
+            .map_err(|source| {
+                PStateError::get_cpu_value(SCALING_AVAILABLE_FREQUENCIES, cpu, path, source)
+            })?;
+
+        frequencies.sort_unstable();
+        Ok(Some(frequencies))
+    }
+
+    /// Get the minimum frequency the governor may select for a CPU, in kHz.
+    pub fn scaling_min_freq(&self, cpu: usize) -> Result<u64, PStateError> {
+        self.cpufreq_value(cpu, SCALING_MIN_FREQ)
+    }
+
+    /// Set the minimum frequency the governor may select for a CPU, in kHz.
+    ///
+    /// Frequencies outside of the hardware limits are rejected with `PStateError::OutOfRange`.
+    pub fn set_scaling_min_freq(&self, cpu: usize, khz: u64) -> Result<(), PStateError> {
+        self.set_cpufreq_khz(cpu, SCALING_MIN_FREQ, khz)
+    }
+
+    /// Get the maximum frequency the governor may select for a CPU, in kHz.
+    pub fn scaling_max_freq(&self, cpu: usize) -> Result<u64, PStateError> {
+        self.cpufreq_value(cpu, SCALING_MAX_FREQ)
+    }
+
+    /// Set the maximum frequency the governor may select for a CPU, in kHz.
+    ///
+    /// Frequencies outside of the hardware limits are rejected with `PStateError::OutOfRange`.
+    pub fn set_scaling_max_freq(&self, cpu: usize, khz: u64) -> Result<(), PStateError> {
+        self.set_cpufreq_khz(cpu, SCALING_MAX_FREQ, khz)
+    }
+
+    /// Set the lower performance limit, either globally as a percent or for every
+    /// online CPU as a frequency.
+    ///
+    /// Percents apply through intel_pstate's global control, whereas frequencies are
+    /// written to the cpufreq policy of each CPU. The two limits are combined by the
+    /// kernel, so setting one does not reset the other.
+    pub fn set_min(&self, bound: PerfBound) -> Result<(), PStateError> {
+        match bound {
+            PerfBound::Percent(min) => self.set_min_perf_pct(min),
+            PerfBound::Khz(khz) => {
+                for cpu in self.online_cpus()? {
+                    self.set_scaling_min_freq(cpu, khz)?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+
+    /// Set the upper performance limit, either globally as a percent or for every
+    /// online CPU as a frequency.
+    ///
+    /// As with [`PState::set_min`], the percent and frequency limits are independent.
+    pub fn set_max(&self, bound: PerfBound) -> Result<(), PStateError> {
+        match bound {
+            PerfBound::Percent(max) => self.set_max_perf_pct(max),
+            PerfBound::Khz(khz) => {
+                for cpu in self.online_cpus()? {
+                    self.set_scaling_max_freq(cpu, khz)?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+
+    fn cpufreq_value<F: FromStr>(&self, cpu: usize, src: &'static str) -> Result<F, PStateError>
+    where
+        F::Err: Display,
+    {
+        let path = self.cpufreq_file(cpu, src);
+        self.parse_file(&path)
+            .map_err(|source| PStateError::get_cpu_value(src, cpu, path, source))
+    }
+
+    fn set_cpufreq_value<V: Display>(
+        &self,
+        cpu: usize,
+        src: &'static str,
+        value: V,
+    ) -> Result<(), PStateError> {
+        let path = self.cpufreq_file(cpu, src);
+        self.write_value(&path, value)
+            .map_err(|source| PStateError::set_cpu_value(src, cpu, path, source))
+    }
+
+    /// Write a frequency after checking it against the hardware limits of the CPU.
+    fn set_cpufreq_khz(&self, cpu: usize, src: &'static str, khz: u64) -> Result<(), PStateError> {
+        let (min, max) = self.cpuinfo_freq_cached(cpu)?;
+
+        if khz < min || khz > max {
+            return Err(PStateError::OutOfRange { src, value: khz });
+        }
+
+        self.set_cpufreq_value(cpu, src, khz)
+    }
+
+    /// Get the energy performance bias (EPB) of a CPU, from 0 (performance) to 15 (power saving).
+    pub fn energy_perf_bias(&self, cpu: usize) -> Result<u8, PStateError> {
+        let path = self.energy_perf_bias_file(cpu);
+        self.parse_file(&path)
+            .map_err(|source| PStateError::get_cpu_value(ENERGY_PERF_BIAS, cpu, path, source))
+    }
+
+    /// Set the energy performance bias (EPB) of a CPU.
+    ///
+    /// Values above 15 are rejected with `PStateError::OutOfRange`.
+    pub fn set_energy_perf_bias(&self, cpu: usize, value: u8) -> Result<(), PStateError> {
+        if value > 15 {
+            return Err(PStateError::OutOfRange {
+                src: ENERGY_PERF_BIAS,
+                value: value.into(),
+            });
+        }
+
+        let path = self.energy_perf_bias_file(cpu);
+        self.write_value(&path, value)
+            .map_err(|source| PStateError::set_cpu_value(ENERGY_PERF_BIAS, cpu, path, source))
+    }
+
+    fn energy_perf_bias_file(&self, cpu: usize) -> PathBuf {
+        self.cpu_dir(cpu).join("power").join(ENERGY_PERF_BIAS)
+    }
+
+    /// Get the cpufreq state of every online CPU, ordered by CPU index.
+    ///
+    /// Offline CPUs are omitted.
+    pub fn per_cpu_values(&self) -> Result<Vec<CpuState>, PStateError> {
+        let hwp = self.hwp_active();
+
+        self.online_cpus()?
+            .into_iter()
+            .map(|cpu| {
+                Ok(CpuState {
+                    cpu,
+                    cur_freq_khz: self.scaling_cur_freq(cpu)?,
+                    min_freq_khz: self.scaling_min_freq(cpu)?,
+                    max_freq_khz: self.scaling_max_freq(cpu)?,
+                    governor: self.scaling_governor(cpu)?,
+                    epp: if hwp { Some(self.epp(cpu)?) } else { None },
+                })
+            })
+            .collect()
+    }
+
+    /// Get the number of times the core of a CPU has been thermally throttled since boot.
+    ///
+    /// `PStateError::Unsupported` is returned if the system does not report it.
+    pub fn throttle_count(&self, cpu: usize) -> Result<u64, PStateError> {
+        let path = self
+            .cpu_dir(cpu)
+            .join("thermal_throttle")
+            .join(CORE_THROTTLE_COUNT);
+
+        if !self.backend.exists(&path) {
+            return Err(PStateError::Unsupported {
+                src: CORE_THROTTLE_COUNT,
+            });
+        }
+
+        self.parse_file(&path)
+            .map_err(|source| PStateError::get_cpu_value(CORE_THROTTLE_COUNT, cpu, path, source))
+    }
+
+    /// Get the sum of the thermal throttle counts of every online CPU.
+    ///
+    /// Sibling threads share a core, and so share a count, which is added once per thread.
+    /// A rising total while a workload runs shows that it is losing performance to heat.
+    pub fn total_throttle_count(&self) -> Result<u64, PStateError> {
+        let mut total = 0;
+
+        for cpu in self.online_cpus()? {
+            total += self.throttle_count(cpu)?;
+        }
+
+        Ok(total)
+    }
+
+    /// Get the number of logical CPUs present in the system, whether online or not.
+    pub fn cpu_count(&self) -> Result<usize, PStateError> {
+        self.cpu_list(PRESENT).map(|cpus| cpus.len())
+    }
+
+    /// Whether a CPU is online.
+    ///
+    /// CPUs which cannot be taken offline, such as CPU0 on most systems, lack an
+    /// `online` file and are always considered online.
+    pub fn cpu_online(&self, cpu: usize) -> Result<bool, PStateError> {
+        let path = self.cpu_dir(cpu).join(ONLINE);
+
+        if !self.backend.exists(&path) {
+            return Ok(true);
+        }
+
+        self.parse_file(&path)
+            .map(|Bool01(online)| online)
+            .map_err(|source| PStateError::get_cpu_value(ONLINE, cpu, path, source))
+    }
+
+    /// The indexes of the CPUs which are present and online.
+    pub(crate) fn online_cpus(&self) -> Result<Vec<usize>, PStateError> {
+        let mut online = Vec::new();
+
+        for cpu in self.cpu_list(PRESENT)? {
+            if self.cpu_online(cpu)? {
+                online.push(cpu);
+            }
+        }
+
+        Ok(online)
+    }
+
+    /// Read a file containing a whitespace-separated list of values.
+    fn parse_list(&self, path: &Path) -> io::Result<Vec<String>> {
+        Ok(self
+            .read(path)?
+            .split_whitespace()
+            .map(String::from)
+            .collect())
+    }
+
+    /// Read a CPU list, such as `online` or `present`.
+    fn cpu_list(&self, src: &'static str) -> Result<Vec<usize>, PStateError> {
+        self.read(&self.cpu_root().join(src))
+            .and_then(|list| parse_cpu_list(&list))
+            .map_err(|source| PStateError::get_value(src, self.cpu_root().join(src), source))
+    }
+}
+
+/// Parse a CPU list such as `0-3,5` into the indexes it contains.
+fn parse_cpu_list(list: &str) -> io::Result<Vec<usize>> {
+    let mut cpus = Vec::new();
+
+    for range in list.trim().split(',').filter(|range| !range.is_empty()) {
+        match range.split_once('-') {
+            Some((start, end)) => {
+                let start: usize = parse_value(start)?;
+                let end: usize = parse_value(end)?;
+                cpus.extend(start..=end);
+            }
+            None => cpus.push(parse_value(range)?),
+        }
+    }
+
+    Ok(cpus)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::*;
+
+    /// A CPU0 which may run from 800 MHz to 4 GHz, limited to 1 GHz to 3 GHz.
+    const FREQS: &[(&str, &str)] = &[
+        ("intel_pstate/status", "active\n"),
+        ("cpu0/cpufreq/cpuinfo_min_freq", "800000\n"),
+        ("cpu0/cpufreq/cpuinfo_max_freq", "4000000\n"),
+        ("cpu0/cpufreq/scaling_min_freq", "1000000\n"),
+        ("cpu0/cpufreq/scaling_max_freq", "3000000\n"),
+    ];
+
+    #[test]
+    fn epp_is_read_per_cpu() {
+        let (pstate, _) = mock(&[
+            ("intel_pstate/status", "active\n"),
+            (
+                "cpu0/cpufreq/energy_performance_preference",
+                "balance_performance\n",
+            ),
+            ("cpu1/cpufreq/energy_performance_preference", "128\n"),
+        ]);
+
+        assert_eq!(pstate.epp(0).unwrap(), Epp::BalancePerformance);
+        assert_eq!(pstate.epp(1).unwrap(), Epp::Raw("128".to_owned()));
+        assert!(matches!(
+            pstate.epp(2),
+            Err(PStateError::Missing {
+                src: ENERGY_PERFORMANCE_PREFERENCE,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn set_epp_writes_the_preference_and_rejects_whitespace() {
+        let (pstate, backend) = mock(&[
+            ("intel_pstate/status", "active\n"),
+            (
+                "cpu0/cpufreq/energy_performance_preference",
+                "balance_performance\n",
+            ),
+        ]);
+
+        pstate.set_epp(0, &Epp::Power).unwrap();
+        assert_eq!(pstate.epp(0).unwrap(), Epp::Power);
+
+        backend.clear_log();
+        assert!(matches!(
+            pstate.set_epp(0, &Epp::Raw("balance power".to_owned())),
+            Err(PStateError::InvalidValue { .. })
+        ));
+        assert!(backend.writes().is_empty());
+    }
+
+    #[test]
+    fn available_epp_lists_each_preference() {
+        let (pstate, _) = mock(&[
+            ("intel_pstate/status", "active\n"),
+            (
+                "cpu0/cpufreq/energy_performance_available_preferences",
+                "default performance balance_performance balance_power power \n",
+            ),
+        ]);
+
+        assert_eq!(
+            pstate.available_epp(0).unwrap(),
+            [
+                Epp::Default,
+                Epp::Performance,
+                Epp::BalancePerformance,
+                Epp::BalancePower,
+                Epp::Power,
+            ]
+        );
+    }
+
+    #[test]
+    fn scaling_governor_is_read_per_cpu() {
+        let (pstate, _) = mock(&[
+            ("intel_pstate/status", "passive\n"),
+            ("cpu0/cpufreq/scaling_governor", "schedutil\n"),
+        ]);
+
+        assert_eq!(pstate.scaling_governor(0).unwrap(), "schedutil");
+        assert!(matches!(
+            pstate.scaling_governor(1),
+            Err(PStateError::Missing {
+                src: SCALING_GOVERNOR,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn set_scaling_governor_accepts_only_available_governors() {
+        let (pstate, backend) = mock(&[
+            ("intel_pstate/status", "passive\n"),
+            (
+                "cpu0/cpufreq/scaling_available_governors",
+                "performance powersave\n",
+            ),
+            ("cpu0/cpufreq/scaling_governor", "powersave\n"),
+        ]);
+
+        pstate.set_scaling_governor(0, "performance").unwrap();
+        assert_eq!(pstate.scaling_governor(0).unwrap(), "performance");
+
+        backend.clear_log();
+        assert!(matches!(
+            pstate.set_scaling_governor(0, "ondemand"),
+            Err(PStateError::InvalidValue {
+                src: SCALING_GOVERNOR,
+                ref value,
+            }) if value == "ondemand"
+        ));
+        assert!(backend.writes().is_empty());
+    }
+
+    #[test]
+    fn available_governors_are_split_on_whitespace() {
+        let (pstate, _) = mock(&[
+            ("intel_pstate/status", "passive\n"),
+            (
+                "cpu0/cpufreq/scaling_available_governors",
+                "conservative ondemand  userspace\tpowersave performance schedutil \n",
+            ),
+        ]);
+
+        assert_eq!(
+            pstate.available_governors(0).unwrap(),
+            [
+                "conservative",
+                "ondemand",
+                "userspace",
+                "powersave",
+                "performance",
+                "schedutil"
+            ]
+        );
+        assert!(matches!(
+            pstate.available_governors(1),
+            Err(PStateError::Missing { .. })
+        ));
+    }
+
+    #[test]
+    fn scaling_cur_freq_is_read_in_khz_and_mhz() {
+        let (pstate, _) = mock(&[
+            ("intel_pstate/status", "active\n"),
+            ("cpu0/cpufreq/scaling_cur_freq", "2400123\n"),
+            ("cpu1/cpufreq/scaling_cur_freq", "fast\n"),
+        ]);
+
+        assert_eq!(pstate.scaling_cur_freq(0).unwrap(), 2_400_123);
+        assert_eq!(pstate.scaling_cur_freq_mhz(0).unwrap(), 2400);
+        assert!(matches!(
+            pstate.scaling_cur_freq(1),
+            Err(PStateError::Parse { .. })
+        ));
+        assert!(matches!(
+            pstate.scaling_cur_freq(2),
+            Err(PStateError::Missing { .. })
+        ));
+    }
+
+    #[test]
+    fn scaling_freqs_are_checked_against_the_hardware_limits() {
+        let (pstate, backend) = mock(FREQS);
+
+        pstate.set_scaling_min_freq(0, 800_000).unwrap();
+        pstate.set_scaling_max_freq(0, 4_000_000).unwrap();
+        assert_eq!(pstate.scaling_min_freq(0).unwrap(), 800_000);
+        assert_eq!(pstate.scaling_max_freq(0).unwrap(), 4_000_000);
+
+        backend.clear_log();
+        assert!(matches!(
+            pstate.set_scaling_max_freq(0, 4_000_001),
+            Err(PStateError::OutOfRange {
+                src: SCALING_MAX_FREQ,
+                value: 4_000_001,
+            })
+        ));
+        assert!(matches!(
+            pstate.set_scaling_min_freq(0, 799_999),
+            Err(PStateError::OutOfRange {
+                src: SCALING_MIN_FREQ,
+                value: 799_999,
+            })
+        ));
+        assert!(backend.writes().is_empty());
+    }
+
+    #[test]
+    fn cpuinfo_freqs_are_read_once_when_cached() {
+        let (pstate, backend) = mock(FREQS);
+
+        assert_eq!(pstate.cpuinfo_min_freq(0).unwrap(), 800_000);
+        assert_eq!(pstate.cpuinfo_max_freq(0).unwrap(), 4_000_000);
+
+        backend.clear_log();
+        assert_eq!(pstate.cpuinfo_freq_cached(0).unwrap(), (800_000, 4_000_000));
+        assert_eq!(backend.reads().len(), 2);
+
+        backend.set(path("cpu0/cpufreq/cpuinfo_max_freq"), "5000000\n");
+        assert_eq!(pstate.cpuinfo_freq_cached(0).unwrap(), (800_000, 4_000_000));
+        assert_eq!(backend.reads().len(), 2);
+    }
+
+    #[test]
+    fn base_frequency_is_none_when_missing() {
+        let (pstate, _) = mock(&[
+            ("intel_pstate/status", "active\n"),
+            ("cpu0/cpufreq/base_frequency", "2100000\n"),
+        ]);
+
+        assert_eq!(pstate.base_frequency(0).unwrap(), Some(2_100_000));
+        assert_eq!(pstate.base_frequency(1).unwrap(), None);
+    }
+
+    #[test]
+    fn perf_pct_converts_to_khz_at_the_midpoint_and_boundaries() {
+        let (pstate, _) = mock(FREQS);
+
+        assert_eq!(pstate.perf_pct_to_khz(0, pct(0)).unwrap(), 800_000);
+        assert_eq!(pstate.perf_pct_to_khz(0, pct(50)).unwrap(), 2_400_000);
+        assert_eq!(pstate.perf_pct_to_khz(0, pct(100)).unwrap(), 4_000_000);
+
+        assert_eq!(pstate.khz_to_perf_pct(0, 2_400_000).unwrap(), pct(50));
+        assert_eq!(pstate.khz_to_perf_pct(0, 2_415_000).unwrap(), pct(50));
+        assert_eq!(pstate.khz_to_perf_pct(0, 2_417_000).unwrap(), pct(51));
+        assert_eq!(pstate.khz_to_perf_pct(0, 100_000).unwrap(), pct(0));
+        assert_eq!(pstate.khz_to_perf_pct(0, 9_000_000).unwrap(), pct(100));
+    }
+
+    #[test]
+    fn cpu_lists_are_parsed_with_ranges() {
+        assert_eq!(parse_cpu_list("0-3,5\n").unwrap(), [0, 1, 2, 3, 5]);
+        assert_eq!(parse_cpu_list("0\n").unwrap(), [0]);
+        assert!(parse_cpu_list("\n").unwrap().is_empty());
+        assert!(parse_cpu_list("0-x").is_err());
+
+        let (pstate, _) = mock(&[("intel_pstate/status", "active\n"), ("present", "0-7\n")]);
+        assert_eq!(pstate.cpu_count().unwrap(), 8);
+    }
+
+    #[test]
+    fn energy_perf_bias_accepts_up_to_15() {
+        let (pstate, backend) = mock(&[
+            ("intel_pstate/status", "active\n"),
+            ("cpu0/power/energy_perf_bias", "6\n"),
+        ]);
+
+        assert_eq!(pstate.energy_perf_bias(0).unwrap(), 6);
+        pstate.set_energy_perf_bias(0, 15).unwrap();
+        assert_eq!(pstate.energy_perf_bias(0).unwrap(), 15);
+
+        backend.clear_log();
+        assert!(matches!(
+            pstate.set_energy_perf_bias(0, 16),
+            Err(PStateError::OutOfRange {
+                src: ENERGY_PERF_BIAS,
+                value: 16,
+            })
+        ));
+        assert!(backend.writes().is_empty());
+    }
+
+    #[test]
+    fn offline_cpus_are_skipped() {
+        let (pstate, backend) = mock(&[
+            ("intel_pstate/status", "active\n"),
+            ("present", "0-2\n"),
+            ("cpu1/online", "0\n"),
+            ("cpu2/online", "1\n"),
+            (
+                "cpu0/cpufreq/energy_performance_preference",
+                "balance_performance\n",
+            ),
+            (
+                "cpu2/cpufreq/energy_performance_preference",
+                "balance_performance\n",
+            ),
+        ]);
+
+        assert!(pstate.cpu_online(0).unwrap());
+        assert!(!pstate.cpu_online(1).unwrap());
+        assert!(pstate.cpu_online(2).unwrap());
+        assert_eq!(pstate.online_cpus().unwrap(), [0, 2]);
+
+        pstate.set_epp_all(&Epp::Power).unwrap();
+        assert_eq!(
+            writes(&backend),
+            [
+                (
+                    "cpu0/cpufreq/energy_performance_preference".to_owned(),
+                    "power".to_owned()
+                ),
+                (
+                    "cpu2/cpufreq/energy_performance_preference".to_owned(),
+                    "power".to_owned()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn frequency_summary_spans_every_online_cpu() {
+        let (pstate, _) = mock(&[
+            ("intel_pstate/status", "active\n"),
+            ("present", "0-2\n"),
+            ("cpu0/cpufreq/scaling_cur_freq", "1000\n"),
+            ("cpu1/cpufreq/scaling_cur_freq", "2000\n"),
+            ("cpu2/cpufreq/scaling_cur_freq", "3000\n"),
+        ]);
+
+        assert_eq!(
+            pstate.frequency_summary().unwrap(),
+            FreqSummary {
+                min_khz: 1000,
+                max_khz: 3000,
+                avg_khz: 2000,
+            }
+        );
+    }
+
+    #[test]
+    fn hwp_active_follows_the_epp_file() {
+        let (pstate, _) = mock(&[
+            ("intel_pstate/status", "active\n"),
+            (
+                "cpu0/cpufreq/energy_performance_preference",
+                "balance_performance\n",
+            ),
+        ]);
+        assert!(pstate.hwp_active());
+
+        let (pstate, _) = mock(&[
+            ("intel_pstate/status", "active\n"),
+            ("cpu0/cpufreq/scaling_governor", "powersave\n"),
+        ]);
+        assert!(!pstate.hwp_active());
+    }
+
+    #[test]
+    fn scaling_driver_names_passive_mode() {
+        let (pstate, _) = mock(&[
+            ("intel_pstate/status", "passive\n"),
+            ("cpu0/cpufreq/scaling_driver", "intel_cpufreq\n"),
+        ]);
+
+        assert_eq!(pstate.scaling_driver(0).unwrap(), "intel_cpufreq");
+    }
+
+    #[test]
+    fn per_cpu_values_reads_each_online_cpu() {
+        let (pstate, _) = mock(&[
+            ("intel_pstate/status", "active\n"),
+            ("present", "0-1\n"),
+            ("cpu0/cpufreq/scaling_cur_freq", "2400000\n"),
+            ("cpu0/cpufreq/scaling_min_freq", "800000\n"),
+            ("cpu0/cpufreq/scaling_max_freq", "4000000\n"),
+            ("cpu0/cpufreq/scaling_governor", "powersave\n"),
+            ("cpu0/cpufreq/energy_performance_preference", "power\n"),
+            ("cpu1/online", "0\n"),
+        ]);
+
+        assert_eq!(
+            pstate.per_cpu_values().unwrap(),
+            [CpuState {
+                cpu: 0,
+                cur_freq_khz: 2_400_000,
+                min_freq_khz: 800_000,
+                max_freq_khz: 4_000_000,
+                governor: "powersave".to_owned(),
+                epp: Some(Epp::Power),
+            }]
+        );
+    }
+
+    #[test]
+    fn epp_round_trips_through_strings() {
+        for preference in &[
+            "default",
+            "performance",
+            "balance_performance",
+            "balance_power",
+            "power",
+            "178",
+        ] {
+            let epp: Epp = preference.parse().unwrap();
+            assert_eq!(epp.to_string(), *preference);
+        }
+
+        assert_eq!("power".parse::<Epp>().unwrap(), Epp::Power);
+        assert_eq!("178".parse::<Epp>().unwrap(), Epp::Raw("178".to_owned()));
+    }
+
+    #[test]
+    fn sample_frequencies_takes_one_sample_per_interval() {
+        use std::cell::Cell;
+
+        let (pstate, backend) = mock(&[
+            ("intel_pstate/status", "active\n"),
+            ("present", "0\n"),
+            ("cpu0/cpufreq/scaling_cur_freq", "2000000\n"),
+        ]);
+
+        let start = Instant::now();
+        let elapsed = Cell::new(Duration::ZERO);
+        let sleeps = Cell::new(Vec::new());
+        let now = || start + elapsed.get();
+        let sleep = |duration: Duration| {
+            let mut slept = sleeps.take();
+            slept.push(duration);
+            sleeps.set(slept);
+            // Each read takes 30 ms, which the schedule absorbs.
+            elapsed.set(elapsed.get() + duration + Duration::from_millis(30));
+        };
+
+        let samples = pstate
+            .sample_frequencies_with(
+                Duration::from_secs(1),
+                Duration::from_millis(100),
+                now,
+                sleep,
+            )
+            .unwrap();
+        assert_eq!(samples.len(), 10);
+        assert_eq!(samples[0].avg_khz, 2_000_000);
+
+        let sleeps = sleeps.take();
+        assert_eq!(sleeps[0], Duration::ZERO);
+        assert!(sleeps[1..]
+            .iter()
+            .all(|&slept| slept == Duration::from_millis(70)));
+
+        backend.set(path("present"), "0-1\n");
+        assert!(pstate
+            .sample_frequencies_with(Duration::ZERO, Duration::from_millis(100), now, |_| ())
+            .is_err());
+        assert!(matches!(
+            pstate.sample_frequencies(Duration::from_secs(1), Duration::ZERO),
+            Err(PStateError::OutOfRange { .. })
+        ));
+    }
+
+    #[test]
+    fn sample_frequencies_stops_when_a_read_fails_partway() {
+        let (pstate, backend) = mock(&[
+            ("intel_pstate/status", "active\n"),
+            ("present", "0-1\n"),
+            ("cpu0/cpufreq/scaling_cur_freq", "2000000\n"),
+            ("cpu1/cpufreq/scaling_cur_freq", "1000000\n"),
+        ]);
+        let cur_freq = path("cpu1/cpufreq/scaling_cur_freq");
+
+        let start = Instant::now();
+        let mut sleeps = 0;
+        let result = pstate.sample_frequencies_with(
+            Duration::from_secs(1),
+            Duration::from_millis(100),
+            || start,
+            |_| {
+                sleeps += 1;
+                if sleeps == 3 {
+                    backend.remove(&cur_freq);
+                }
+            },
+        );
+
+        match result {
+            Err(PStateError::Missing { src, path }) => {
+                assert_eq!(src, SCALING_CUR_FREQ);
+                assert_eq!(path, cur_freq);
+            }
+            _ => panic!("expected cpu1's scaling_cur_freq to be missing"),
+        }
+        assert_eq!(sleeps, 3);
+    }
+
+    #[test]
+    fn perf_bounds_route_percents_globally_and_frequencies_per_cpu() {
+        let mut files = FREQS.to_vec();
+        files.extend_from_slice(&[
+            ("present", "0\n"),
+            ("intel_pstate/min_perf_pct", "20\n"),
+            ("intel_pstate/max_perf_pct", "80\n"),
+        ]);
+        let (pstate, backend) = mock(&files);
+
+        pstate.set_max(PerfBound::Percent(pct(60))).unwrap();
+        pstate.set_min(PerfBound::Khz(1_200_000)).unwrap();
+        pstate.set_max(PerfBound::Khz(2_000_000)).unwrap();
+        pstate.set_min(PerfBound::Percent(pct(30))).unwrap();
+
+        assert_eq!(
+            writes(&backend),
+            [
+                ("intel_pstate/max_perf_pct".to_owned(), "60".to_owned()),
+                (
+                    "cpu0/cpufreq/scaling_min_freq".to_owned(),
+                    "1200000".to_owned()
+                ),
+                (
+                    "cpu0/cpufreq/scaling_max_freq".to_owned(),
+                    "2000000".to_owned()
+                ),
+                ("intel_pstate/min_perf_pct".to_owned(), "30".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn throttle_counts_are_summed_or_unsupported() {
+        let (pstate, _) = mock(&[
+            ("intel_pstate/status", "active\n"),
+            ("present", "0-1\n"),
+            ("cpu0/thermal_throttle/core_throttle_count", "3\n"),
+            ("cpu1/thermal_throttle/core_throttle_count", "4\n"),
+        ]);
+        assert_eq!(pstate.throttle_count(1).unwrap(), 4);
+        assert_eq!(pstate.total_throttle_count().unwrap(), 7);
+
+        assert!(matches!(
+            pstate.throttle_count(2),
+            Err(PStateError::Unsupported {
+                src: CORE_THROTTLE_COUNT
+            })
+        ));
+    }
+
+    #[test]
+    fn boosting_compares_each_cpu_with_its_base_frequency() {
+        let (pstate, backend) = mock(&[
+            ("intel_pstate/status", "active\n"),
+            ("present", "0-2\n"),
+            ("cpu0/cpufreq/base_frequency", "2000000\n"),
+            ("cpu0/cpufreq/scaling_cur_freq", "2000000\n"),
+            ("cpu1/cpufreq/scaling_cur_freq", "4000000\n"),
+            ("cpu2/cpufreq/base_frequency", "2000000\n"),
+            ("cpu2/cpufreq/scaling_cur_freq", "1500000\n"),
+        ]);
+        assert!(!pstate.is_boosting().unwrap());
+
+        backend.set(path("cpu2/cpufreq/scaling_cur_freq"), "2000001\n");
+        assert!(pstate.is_boosting().unwrap());
+    }
+
+    #[test]
+    fn available_frequencies_are_sorted_when_listed() {
+        let (pstate, _) = mock(&[
+            ("intel_pstate/status", "off\n"),
+            (
+                "cpu0/cpufreq/scaling_available_frequencies",
+                "3000000 1000000 2000000 \n",
+            ),
+        ]);
+
+        assert_eq!(
+            pstate.available_frequencies(0).unwrap(),
+            Some(vec![1_000_000, 2_000_000, 3_000_000])
+        );
+        assert_eq!(pstate.available_frequencies(1).unwrap(), None);
+    }
+
+    #[test]
+    fn hwp_request_round_trips_and_rejects_out_of_range() {
+        let mut files = FREQS.to_vec();
+        files.push((
+            "cpu0/cpufreq/energy_performance_preference",
+            "balance_performance\n",
+        ));
+        let (pstate, backend) = mock(&files);
+
+        let request = HwpRequest {
+            min_freq: 3_500_000,
+            max_freq: 4_000_000,
+            epp: Epp::Performance,
+        };
+        pstate.set_hwp_request(0, &request).unwrap();
+        assert_eq!(pstate.hwp_request(0).unwrap(), request);
+        assert_eq!(
+            writes(&backend)[..2],
+            [
+                (
+                    "cpu0/cpufreq/scaling_max_freq".to_owned(),
+                    "4000000".to_owned()
+                ),
+                (
+                    "cpu0/cpufreq/scaling_min_freq".to_owned(),
+                    "3500000".to_owned()
+                ),
+            ]
+        );
+
+        backend.clear_log();
+        let too_high = HwpRequest {
+            max_freq: 4_000_001,
+            ..request.clone()
+        };
+        assert!(matches!(
+            pstate.set_hwp_request(0, &too_high),
+            Err(PStateError::OutOfRange {
+                src: SCALING_MAX_FREQ,
+                ..
+            })
+        ));
+        let inverted = HwpRequest {
+            min_freq: 2_000_000,
+            max_freq: 1_000_000,
+            ..request
+        };
+        assert!(pstate.set_hwp_request(0, &inverted).is_err());
+        assert!(backend.writes().is_empty());
+    }
+
+    #[test]
+    fn wait_until_max_below_returns_once_settled() {
+        let (pstate, backend) = mock(&[
+            ("intel_pstate/status", "active\n"),
+            ("present", "0\n"),
+            ("cpu0/cpufreq/scaling_cur_freq", "3000000\n"),
+        ]);
+
+        assert!(pstate
+            .wait_until_max_below(4_000_000, Duration::ZERO)
+            .unwrap());
+        assert!(!pstate
+            .wait_until_max_below(2_000_000, Duration::ZERO)
+            .unwrap());
+
+        let settle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(30));
+            backend.set(path("cpu0/cpufreq/scaling_cur_freq"), "1500000\n");
+        });
+        assert!(pstate
+            .wait_until_max_below(2_000_000, Duration::from_secs(5))
+            .unwrap());
+        settle.join().unwrap();
+    }
+
+    #[test]
+    fn effective_max_mhz_is_capped_by_base_without_turbo() {
+        let mut files = FREQS.to_vec();
+        files.extend_from_slice(&[
+            ("intel_pstate/max_perf_pct", "50\n"),
+            ("intel_pstate/no_turbo", "0\n"),
+            ("cpu0/cpufreq/base_frequency", "2100000\n"),
+        ]);
+        let (pstate, _) = mock(&files);
+
+        assert_eq!(pstate.effective_max_mhz().unwrap(), 2400);
+        pstate.set_no_turbo(true).unwrap();
+        assert_eq!(pstate.effective_max_mhz().unwrap(), 2100);
+    }
+
+    #[test]
+    fn global_freq_range_spans_both_core_types() {
+        let (pstate, _) = mock(&[
+            ("intel_pstate/status", "active\n"),
+            ("present", "0-1\n"),
+            ("cpu0/cpufreq/cpuinfo_min_freq", "400000\n"),
+            ("cpu0/cpufreq/cpuinfo_max_freq", "4700000\n"),
+            ("cpu1/cpufreq/cpuinfo_min_freq", "300000\n"),
+            ("cpu1/cpufreq/cpuinfo_max_freq", "3500000\n"),
+        ]);
+
+        assert_eq!(pstate.global_freq_range().unwrap(), (300_000, 4_700_000));
+    }
+
+    #[test]
+    fn core_types_classifies_two_performance_and_two_efficiency_cores() {
+        let (pstate, backend) = mock(&[
+            ("intel_pstate/status", "active\n"),
+            ("present", "0-3\n"),
+            ("cpu0/cpufreq/base_frequency", "2100000\n"),
+            ("cpu1/cpufreq/base_frequency", "2100000\n"),
+            ("cpu2/cpufreq/base_frequency", "1600000\n"),
+            ("cpu3/cpufreq/base_frequency", "1600000\n"),
+        ]);
+        let expected = [
+            (0, CoreType::Performance),
+            (1, CoreType::Performance),
+            (2, CoreType::Efficiency),
+            (3, CoreType::Efficiency),
+        ];
+
+        assert_eq!(pstate.core_types().unwrap(), expected);
+
+        // The PMUs take precedence over the base frequencies.
+        backend.set(path("cpu2/cpufreq/base_frequency"), "2100000\n");
+        backend.set(path("/sys/devices/cpu_core/cpus"), "0-1\n");
+        backend.set(path("/sys/devices/cpu_atom/cpus"), "2-3\n");
+        assert_eq!(pstate.core_types().unwrap(), expected);
+    }
+
+    #[test]
+    fn core_types_omits_offline_cpus() {
+        let (pstate, _) = mock(&[
+            ("intel_pstate/status", "active\n"),
+            ("present", "0-3\n"),
+            ("cpu2/online", "0\n"),
+            ("cpu0/cpufreq/base_frequency", "2100000\n"),
+            ("cpu1/cpufreq/base_frequency", "2100000\n"),
+            ("cpu3/cpufreq/base_frequency", "1600000\n"),
+        ]);
+
+        assert_eq!(
+            pstate.core_types().unwrap(),
+            [
+                (0, CoreType::Performance),
+                (1, CoreType::Performance),
+                (3, CoreType::Efficiency),
+            ]
+        );
+    }
+
+    #[test]
+    fn global_epp_reads_cpu0_only_with_hwp() {
+        let (pstate, _) = mock(&[
+            ("intel_pstate/status", "active\n"),
+            (
+                "cpu0/cpufreq/energy_performance_preference",
+                "balance_power\n",
+            ),
+            ("cpu1/cpufreq/energy_performance_preference", "power\n"),
+        ]);
+        assert_eq!(pstate.global_epp().unwrap(), Some(Epp::BalancePower));
+
+        let (pstate, _) = mock(FREQS);
+        assert_eq!(pstate.global_epp().unwrap(), None);
+    }
+}
@@ -0,0 +1,273 @@
+// Copyright 2022 System76 <info@system76.com>
+// SPDX-License-Identifier: MIT
+
+//! Access to the driver through a pre-opened directory file descriptor.
+
+use crate::*;
+
+use std::{
+    ffi::CString,
+    fs::{self, File},
+    io::Read,
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd},
+        unix::{ffi::OsStrExt, fs::FileExt},
+    },
+    path::Component,
+};
+
+/// Resolves every path relative to a directory descriptor with `openat`, rather
+/// than by looking the path up again each time.
+struct DirFdBackend {
+    dir: OwnedFd,
+    /// The parent of `dir`, through which the per-CPU directories are reached.
+    parent: OwnedFd,
+    root: PathBuf,
+}
+
+impl DirFdBackend {
+    /// The descriptor that a path is beneath, and the path relative to it, as accepted
+    /// by the `*at` system calls.
+    ///
+    /// Paths in the CPU directories are reached through the parent of the driver directory.
+    /// Any other path is unsupported, as it cannot be reached through the descriptors, and
+    /// a path with any component other than a plain name is refused so that it cannot
+    /// climb out of them.
+    fn relative(&self, path: &Path) -> io::Result<(&OwnedFd, CString)> {
+        let (dir, relative) = if let Ok(relative) = path.strip_prefix(&self.root) {
+            (&self.dir, relative)
+        } else if let Some(relative) = self
+            .root
+            .parent()
+            .and_then(|parent| path.strip_prefix(parent).ok())
+        {
+            (&self.parent, relative)
+        } else {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("{} is outside of the directory descriptor", path.display()),
+            ));
+        };
+
+        if !relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("{} leaves the directory descriptor", path.display()),
+            ));
+        }
+
+        let relative = if relative.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            relative
+        };
+
+        let relative = CString::new(relative.as_os_str().as_bytes())
+            .map_err(|why| io::Error::new(io::ErrorKind::InvalidInput, why))?;
+
+        Ok((dir, relative))
+    }
+
+    /// Open a file beneath its directory descriptor.
+    ///
+    /// `openat2` with `RESOLVE_BENEATH` refuses symlinks which lead out of the directory.
+    /// On kernels older than 5.6, which lack it, `openat` with `O_NOFOLLOW` is used
+    /// instead, which refuses a symlink as the final component.
+    fn open(&self, path: &Path, flags: libc::c_int) -> io::Result<File> {
+        let (dir, relative) = self.relative(path)?;
+        let flags = flags | libc::O_CLOEXEC;
+
+        // SAFETY: `open_how` is a plain C struct, for which all zeroes is valid.
+        let mut how: libc::open_how = unsafe { std::mem::zeroed() };
+        how.flags = flags as u64;
+        how.resolve = libc::RESOLVE_BENEATH;
+
+        // SAFETY: `relative` is a NUL-terminated string and `how` is an `open_how` of the
+        // size given, both of which outlive the call, and `dir` is an open descriptor.
+        let mut fd = unsafe {
+            libc::syscall(
+                libc::SYS_openat2,
+                dir.as_raw_fd(),
+                relative.as_ptr(),
+                &how as *const libc::open_how,
+                std::mem::size_of::<libc::open_how>(),
+            )
+        } as libc::c_int;
+
+        if fd < 0 && io::Error::last_os_error().raw_os_error() == Some(libc::ENOSYS) {
+            // SAFETY: `relative` is a NUL-terminated string which outlives the call, and
+            // `dir` is an open descriptor.
+            fd = unsafe {
+                libc::openat(dir.as_raw_fd(), relative.as_ptr(), flags | libc::O_NOFOLLOW)
+            };
+        }
+
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // SAFETY: the open succeeded, so `fd` is a new descriptor which nothing else owns.
+        Ok(unsafe { File::from_raw_fd(fd) })
+    }
+
+    fn stat(&self, path: &Path) -> io::Result<fs::Metadata> {
+        self.open(path, libc::O_PATH)?.metadata()
+    }
+}
+
+impl PStateBackend for DirFdBackend {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        let mut contents = String::new();
+        self.open(path, libc::O_RDONLY)?
+            .read_to_string(&mut contents)?;
+        Ok(contents)
+    }
+
+    fn write(&self, path: &Path, value: &str) -> io::Result<()> {
+        self.open(path, libc::O_WRONLY | libc::O_TRUNC)?
+            .write_all_at(value.as_bytes(), 0)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.stat(path).is_ok()
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.stat(path).is_ok_and(|metadata| metadata.is_dir())
+    }
+
+    fn can_write(&self, path: &Path) -> bool {
+        // SAFETY: `relative` is a NUL-terminated string which outlives the call, and
+        // `dir` is an open descriptor.
+        self.relative(path).is_ok_and(|(dir, relative)| unsafe {
+            libc::faccessat(
+                dir.as_raw_fd(),
+                relative.as_ptr(),
+                libc::W_OK,
+                libc::AT_EACCESS | libc::AT_SYMLINK_NOFOLLOW,
+            ) == 0
+        })
+    }
+}
+
+impl PState {
+    /// Create a handle which accesses the driver through an open descriptor of its
+    /// directory, such as `/sys/devices/system/cpu/intel_pstate/`.
+    ///
+    /// Every file is opened relative to the descriptor with `openat`, so the path is
+    /// never resolved again. This suits privileged helpers which open the directory
+    /// before dropping their other privileges. Per-CPU files are reached through the
+    /// parent of the directory, which is opened once here. Paths with `..` components,
+    /// and symlinks which lead out of either directory, are refused. Errors report paths
+    /// as though the directory were at its usual location in `/sys/devices/system/cpu/`.
+    ///
+    /// Paths outside of the parent directory cannot be reached, so listing the driver's
+    /// attributes, telling hybrid cores apart by their PMUs, and telling whether the
+    /// system is on AC power are not supported.
+    pub fn from_dir_fd(dir: OwnedFd, driver: PStateDriver) -> Result<PState, PStateError> {
+        let root = Path::new("/sys/devices/system/cpu").join(driver.name());
+
+        // SAFETY: the path is a NUL-terminated string, and `dir` is an open descriptor.
+        let parent = unsafe {
+            libc::openat(
+                dir.as_raw_fd(),
+                b"..\0".as_ptr().cast(),
+                libc::O_PATH | libc::O_DIRECTORY | libc::O_CLOEXEC,
+            )
+        };
+
+        if parent < 0 {
+            return Err(PStateError::NotFound {
+                detected_driver: None,
+            });
+        }
+
+        // SAFETY: `openat` succeeded, so `parent` is a new descriptor which nothing else owns.
+        let parent = unsafe { OwnedFd::from_raw_fd(parent) };
+
+        PState::with_backend(root.clone(), DirFdBackend { dir, parent, root })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::*;
+
+    use std::os::unix::fs::symlink;
+
+    /// A driver directory and a CPU directory in a temporary directory, along with a
+    /// secret file beside them which must not be reachable.
+    fn tempdir() -> (tempfile::TempDir, PState) {
+        let dir = tempfile::tempdir().unwrap();
+        let cpu = dir.path().join("cpu");
+        let driver = cpu.join("intel_pstate");
+        fs::create_dir_all(&driver).unwrap();
+        fs::create_dir_all(cpu.join("cpufreq/policy0")).unwrap();
+        fs::create_dir(cpu.join("cpu0")).unwrap();
+        // As in sysfs, where each CPU links to its policy.
+        symlink("../cpufreq/policy0", cpu.join("cpu0/cpufreq")).unwrap();
+        fs::write(driver.join("max_perf_pct"), "80\n").unwrap();
+        fs::write(cpu.join("cpufreq/policy0/scaling_governor"), "powersave\n").unwrap();
+        fs::write(dir.path().join("secret"), "hunter2\n").unwrap();
+
+        let fd = OwnedFd::from(File::open(&driver).unwrap());
+        let pstate = PState::from_dir_fd(fd, PStateDriver::IntelPState).unwrap();
+        (dir, pstate)
+    }
+
+    #[test]
+    fn dir_fd_reaches_the_driver_and_cpu_directories_only() {
+        let (dir, pstate) = tempdir();
+        let driver = dir.path().join("cpu/intel_pstate");
+
+        assert_eq!(pstate.max_perf_pct().unwrap(), pct(80));
+        pstate.set_max_perf_pct(pct(60)).unwrap();
+        assert_eq!(
+            fs::read_to_string(driver.join("max_perf_pct")).unwrap(),
+            "60"
+        );
+        assert_eq!(pstate.scaling_governor(0).unwrap(), "powersave");
+
+        assert_eq!(
+            pstate
+                .backend
+                .read(Path::new("/etc/hostname"))
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::Unsupported
+        );
+        assert!(matches!(
+            pstate.on_ac_power(),
+            Err(PStateError::Unsupported {
+                src: "power_supply"
+            })
+        ));
+    }
+
+    #[test]
+    fn dir_fd_refuses_to_leave_the_directories() {
+        let (dir, pstate) = tempdir();
+
+        assert!(pstate.read_raw("../../secret").is_err());
+        assert!(pstate.write_raw("../../secret", "0").is_err());
+        assert!(pstate.read_raw("../cpu0/../../secret").is_err());
+        assert!(!pstate.backend.exists(&path("cpu0/../../secret")));
+
+        symlink(
+            dir.path().join("secret"),
+            dir.path().join("cpu/intel_pstate/escape"),
+        )
+        .unwrap();
+        assert!(pstate.read_raw("escape").is_err());
+        assert!(pstate.write_raw("escape", "0").is_err());
+
+        assert_eq!(
+            fs::read_to_string(dir.path().join("secret")).unwrap(),
+            "hunter2\n"
+        );
+    }
+}
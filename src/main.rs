@@ -0,0 +1,193 @@
+// Copyright 2022 System76 <info@system76.com>
+// SPDX-License-Identifier: MIT
+
+//! Command-line interface to the intel-pstate crate.
+
+use intel_pstate::{PState, PStateError, PStateValuesDiff, Percent};
+use std::{convert::TryFrom, env, process};
+
+const USAGE: &str = "usage: intel-pstate <command>
+
+commands:
+    get       print the current values
+    set       set values: [--min PCT] [--max PCT] [--no-turbo BOOL] [--hwp-boost BOOL]
+    status    print the operation mode of the driver
+    report    print the state of the driver and every CPU";
+
+/// An error which ends the program.
+#[derive(Debug)]
+enum Error {
+    /// The arguments were invalid; the usage is printed along with the message.
+    Usage(String),
+    PState(PStateError),
+}
+
+impl From<PStateError> for Error {
+    fn from(why: PStateError) -> Self {
+        Error::PState(why)
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    match run(&args) {
+        Ok(()) => (),
+        Err(Error::Usage(why)) => {
+            eprintln!("intel-pstate: {}\n\n{}", why, USAGE);
+            process::exit(2);
+        }
+        Err(Error::PState(why)) => {
+            eprintln!("intel-pstate: {}", why);
+            process::exit(1);
+        }
+    }
+}
+
+fn run(args: &[String]) -> Result<(), Error> {
+    let (command, args) = args
+        .split_first()
+        .ok_or_else(|| Error::Usage("no command given".into()))?;
+
+    match command.as_str() {
+        "get" => println!("{}", PState::detect()?.values()?),
+        "set" => set(args)?,
+        "status" => println!("{}", PState::detect()?.status()?),
+        "report" => print!("{}", PState::detect()?.report()?),
+        "help" | "-h" | "--help" => println!("{}", USAGE),
+        _ => return Err(Error::Usage(format!("unknown command '{}'", command))),
+    }
+
+    Ok(())
+}
+
+fn set(args: &[String]) -> Result<(), Error> {
+    let values = parse_set_args(args)?;
+    let pstate = PState::detect()?;
+    let mut batch = pstate.batch();
+
+    if let Some(min) = values.min_perf_pct {
+        batch = batch.min_perf_pct(min);
+    }
+
+    if let Some(max) = values.max_perf_pct {
+        batch = batch.max_perf_pct(max);
+    }
+
+    if let Some(no_turbo) = values.no_turbo {
+        batch = batch.no_turbo(no_turbo);
+    }
+
+    if let Some(boost) = values.hwp_dynamic_boost {
+        batch = batch.hwp_dynamic_boost(boost);
+    }
+
+    batch.commit()?;
+    println!("{}", pstate.values()?);
+    Ok(())
+}
+
+/// Parse the options of `set` into the values which were given.
+fn parse_set_args(args: &[String]) -> Result<PStateValuesDiff, Error> {
+    if args.is_empty() {
+        return Err(Error::Usage("set requires at least one value".into()));
+    }
+
+    let mut values = PStateValuesDiff::default();
+    let mut args = args.iter();
+
+    while let Some(flag) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| Error::Usage(format!("{} requires a value", flag)))?;
+
+        match flag.as_str() {
+            "--min" => values.min_perf_pct = Some(parse_percent(flag, value)?),
+            "--max" => values.max_perf_pct = Some(parse_percent(flag, value)?),
+            "--no-turbo" => values.no_turbo = Some(parse_bool(flag, value)?),
+            "--hwp-boost" => values.hwp_dynamic_boost = Some(parse_bool(flag, value)?),
+            _ => return Err(Error::Usage(format!("unknown option '{}'", flag))),
+        }
+    }
+
+    Ok(values)
+}
+
+fn parse_percent(flag: &str, value: &str) -> Result<Percent, Error> {
+    value
+        .parse::<u8>()
+        .ok()
+        .and_then(|value| Percent::try_from(value).ok())
+        .ok_or_else(|| Error::Usage(format!("{} must be a percent from 0 to 100", flag)))
+}
+
+fn parse_bool(flag: &str, value: &str) -> Result<bool, Error> {
+    match value {
+        "true" | "1" | "on" => Ok(true),
+        "false" | "0" | "off" => Ok(false),
+        _ => Err(Error::Usage(format!("{} must be true or false", flag))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|&arg| arg.to_owned()).collect()
+    }
+
+    fn usage<T>(result: Result<T, Error>) -> String {
+        match result {
+            Err(Error::Usage(why)) => why,
+            Err(Error::PState(why)) => panic!("unexpected error: {}", why),
+            Ok(_) => panic!("unexpected success"),
+        }
+    }
+
+    #[test]
+    fn set_args_are_parsed_into_the_given_values() {
+        let values = parse_set_args(&args(&["--max", "80", "--no-turbo", "on"])).unwrap();
+        assert_eq!(
+            values,
+            PStateValuesDiff {
+                max_perf_pct: Percent::new(80),
+                no_turbo: Some(true),
+                ..PStateValuesDiff::default()
+            }
+        );
+
+        let values = parse_set_args(&args(&["--min", "0", "--hwp-boost", "false"])).unwrap();
+        assert_eq!(values.min_perf_pct, Some(Percent::MIN));
+        assert_eq!(values.hwp_dynamic_boost, Some(false));
+    }
+
+    #[test]
+    fn invalid_args_are_usage_errors() {
+        assert_eq!(usage(run(&[])), "no command given");
+        assert_eq!(
+            usage(run(&args(&["frobnicate"]))),
+            "unknown command 'frobnicate'"
+        );
+        assert_eq!(
+            usage(parse_set_args(&[])),
+            "set requires at least one value"
+        );
+        assert_eq!(
+            usage(parse_set_args(&args(&["--max"]))),
+            "--max requires a value"
+        );
+        assert_eq!(
+            usage(parse_set_args(&args(&["--max", "101"]))),
+            "--max must be a percent from 0 to 100"
+        );
+        assert_eq!(
+            usage(parse_set_args(&args(&["--no-turbo", "maybe"]))),
+            "--no-turbo must be true or false"
+        );
+        assert_eq!(
+            usage(parse_set_args(&args(&["--turbo", "1"]))),
+            "unknown option '--turbo'"
+        );
+    }
+}
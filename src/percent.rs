@@ -0,0 +1,119 @@
+// Copyright 2022 System76 <info@system76.com>
+// SPDX-License-Identifier: MIT
+
+use crate::PStateError;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::{
+    convert::TryFrom,
+    fmt::{self, Display},
+    str::FromStr,
+};
+
+const PERCENT: &str = "percent";
+
+/// A percentage, which is always within `0..=100`.
+///
+/// It is displayed and serialized as a plain integer, without a `%` sign, and
+/// deserializing a value above 100 fails.
+#[derive(Copy, Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Deserialize, Serialize),
+    serde(try_from = "u8", into = "u8")
+)]
+pub struct Percent(pub(crate) u8);
+
+impl Percent {
+    /// 0%.
+    pub const MIN: Percent = Percent(0);
+
+    /// 100%.
+    pub const MAX: Percent = Percent(100);
+
+    /// Create a percentage, or `None` if the value exceeds 100.
+    pub fn new(value: u8) -> Option<Percent> {
+        if value <= 100 {
+            Some(Percent(value))
+        } else {
+            None
+        }
+    }
+
+    /// The percentage as an integer.
+    pub fn get(self) -> u8 {
+        self.0
+    }
+}
+
+impl TryFrom<u8> for Percent {
+    type Error = PStateError;
+
+    fn try_from(value: u8) -> Result<Percent, PStateError> {
+        Percent::new(value).ok_or(PStateError::OutOfRange {
+            src: PERCENT,
+            value: value.into(),
+        })
+    }
+}
+
+impl From<Percent> for u8 {
+    fn from(percent: Percent) -> u8 {
+        percent.0
+    }
+}
+
+impl FromStr for Percent {
+    type Err = PStateError;
+
+    fn from_str(s: &str) -> Result<Percent, PStateError> {
+        let value = s.parse::<u8>().map_err(|_| PStateError::InvalidValue {
+            src: PERCENT,
+            value: s.to_owned(),
+        })?;
+
+        Percent::try_from(value)
+    }
+}
+
+impl Display for Percent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percents_are_within_0_to_100() {
+        assert_eq!(Percent::new(0), Some(Percent::MIN));
+        assert_eq!(Percent::new(100), Some(Percent::MAX));
+        assert_eq!(Percent::new(101), None);
+
+        assert_eq!(Percent::try_from(42).unwrap().get(), 42);
+        assert!(matches!(
+            Percent::try_from(255),
+            Err(PStateError::OutOfRange {
+                src: PERCENT,
+                value: 255,
+            })
+        ));
+    }
+
+    #[test]
+    fn percents_are_parsed_and_displayed_as_integers() {
+        assert_eq!("75".parse::<Percent>().unwrap(), Percent(75));
+        assert_eq!(Percent(75).to_string(), "75");
+        assert!(matches!(
+            "75%".parse::<Percent>(),
+            Err(PStateError::InvalidValue { src: PERCENT, .. })
+        ));
+        assert!(matches!(
+            "101".parse::<Percent>(),
+            Err(PStateError::OutOfRange { value: 101, .. })
+        ));
+    }
+}
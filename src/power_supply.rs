@@ -0,0 +1,148 @@
+// Copyright 2022 System76 <info@system76.com>
+// SPDX-License-Identifier: MIT
+
+//! Selection of values by power source, using `/sys/class/power_supply/`.
+
+use crate::*;
+
+const POWER_SUPPLY: &str = "power_supply";
+const POWER_SUPPLY_DIR: &str = "/sys/class/power_supply";
+
+impl PState {
+    /// Whether the system is running on AC power.
+    ///
+    /// The system is on AC power if any adapter reports that it is online. Systems
+    /// without any adapter, such as most desktops, are always considered to be on AC
+    /// power. Batteries, and the power supplies of peripherals such as wireless mice,
+    /// are ignored.
+    ///
+    /// If the power supplies cannot be listed, such as through a handle from
+    /// [`PState::from_dir_fd`], `PStateError::Unsupported` is returned rather than
+    /// guessing.
+    pub fn on_ac_power(&self) -> Result<bool, PStateError> {
+        let root = Path::new(POWER_SUPPLY_DIR);
+        let unsupported = PStateError::Unsupported { src: POWER_SUPPLY };
+
+        if !self.backend.is_dir(root) {
+            return Err(unsupported);
+        }
+
+        let supplies = self
+            .backend
+            .list_dirs(root)
+            .map_err(|source| match source.kind() {
+                io::ErrorKind::NotFound | io::ErrorKind::Unsupported => unsupported,
+                _ => PStateError::get_value(POWER_SUPPLY, root.to_owned(), source),
+            })?;
+
+        let mut has_adapter = false;
+
+        for supply in supplies {
+            let dir = root.join(supply);
+            let attr = |name: &str| self.read(&dir.join(name)).ok();
+
+            let is_battery = attr("type").is_some_and(|kind| kind.trim() == "Battery");
+            let is_peripheral = attr("scope").is_some_and(|scope| scope.trim() == "Device");
+
+            if is_battery || is_peripheral {
+                continue;
+            }
+
+            let path = dir.join("online");
+            if !self.backend.exists(&path) {
+                continue;
+            }
+
+            has_adapter = true;
+
+            let online: u8 = self
+                .parse_file(&path)
+                .map_err(|source| PStateError::get_value(POWER_SUPPLY, path, source))?;
+
+            if online != 0 {
+                return Ok(true);
+            }
+        }
+
+        Ok(!has_adapter)
+    }
+
+    /// Set the values of `ac` when on AC power, or those of `battery` otherwise.
+    ///
+    /// See [`PState::on_ac_power`] for how the power source is determined. The
+    /// values are set with [`PState::set_values`].
+    pub fn apply_power_source_profile(
+        &self,
+        ac: &PStateValues,
+        battery: &PStateValues,
+    ) -> Result<(), PStateError> {
+        let values = if self.on_ac_power()? { ac } else { battery };
+        self.set_values(*values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::*;
+    use crate::*;
+
+    /// A laptop with an adapter, a battery, and a wireless mouse.
+    fn laptop(online: &str) -> (PState, MemoryBackend) {
+        let mut files = VALUES.to_vec();
+        files.extend_from_slice(&[
+            ("/sys/class/power_supply/AC/type", "Mains\n"),
+            ("/sys/class/power_supply/AC/online", online),
+            ("/sys/class/power_supply/BAT0/type", "Battery\n"),
+            ("/sys/class/power_supply/BAT0/online", "1\n"),
+            ("/sys/class/power_supply/hidpp_battery_0/scope", "Device\n"),
+            ("/sys/class/power_supply/hidpp_battery_0/online", "1\n"),
+        ]);
+        mock(&files)
+    }
+
+    #[test]
+    fn power_source_selects_the_profile() {
+        let ac = PStateValues::default().max_perf_pct(pct(100));
+        let battery = PStateValues::default().max_perf_pct(pct(50)).no_turbo(true);
+
+        let (pstate, _) = laptop("1\n");
+        assert!(pstate.on_ac_power().unwrap());
+        pstate.apply_power_source_profile(&ac, &battery).unwrap();
+        assert_eq!(pstate.max_perf_pct().unwrap(), pct(100));
+
+        let (pstate, _) = laptop("0\n");
+        assert!(!pstate.on_ac_power().unwrap());
+        pstate.apply_power_source_profile(&ac, &battery).unwrap();
+        assert_eq!(pstate.max_perf_pct().unwrap(), pct(50));
+        assert!(pstate.no_turbo().unwrap());
+    }
+
+    #[test]
+    fn systems_without_an_adapter_are_on_ac_power() {
+        let mut files = VALUES.to_vec();
+        files.push(("/sys/class/power_supply/hidpp_battery_0/scope", "Device\n"));
+        let (pstate, _) = mock(&files);
+        assert!(pstate.on_ac_power().unwrap());
+    }
+
+    #[test]
+    fn unreachable_power_supplies_are_unsupported() {
+        let ac = PStateValues::default().max_perf_pct(pct(100));
+        let battery = PStateValues::default().max_perf_pct(pct(50));
+        let (pstate, backend) = mock(VALUES);
+
+        assert!(matches!(
+            pstate.on_ac_power(),
+            Err(PStateError::Unsupported {
+                src: "power_supply"
+            })
+        ));
+        assert!(matches!(
+            pstate.apply_power_source_profile(&ac, &battery),
+            Err(PStateError::Unsupported {
+                src: "power_supply"
+            })
+        ));
+        assert!(writes(&backend).is_empty());
+    }
+}
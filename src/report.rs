@@ -0,0 +1,140 @@
+// Copyright 2022 System76 <info@system76.com>
+// SPDX-License-Identifier: MIT
+
+//! A summary of the driver's global and per-CPU state, for diagnostics.
+
+use crate::*;
+
+/// The state of the driver and every online CPU, as returned by [`PState::report`].
+///
+/// Fields which are unsupported by the system, or which could not be read, are `None`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct PStateReport {
+    pub driver: PStateDriver,
+    pub status: Option<PStateStatus>,
+    pub values: Option<PStateValues>,
+    pub turbo_pct: Option<u8>,
+    pub num_pstates: Option<u32>,
+    pub cpus: Vec<CpuReport>,
+}
+
+/// The state of a single CPU within a [`PStateReport`].
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct CpuReport {
+    pub cpu: usize,
+    pub cur_freq_khz: Option<u64>,
+    pub governor: Option<String>,
+    pub epp: Option<Epp>,
+}
+
+impl PState {
+    /// Gather the global and per-CPU state into a single report.
+    ///
+    /// Only a failure to list the online CPUs is returned as an error. Every other
+    /// value that cannot be read is left out of the report.
+    pub fn report(&self) -> Result<PStateReport, PStateError> {
+        let cpus = self
+            .online_cpus()?
+            .into_iter()
+            .map(|cpu| CpuReport {
+                cpu,
+                cur_freq_khz: self.scaling_cur_freq(cpu).ok(),
+                governor: self.scaling_governor(cpu).ok(),
+                epp: self.epp(cpu).ok(),
+            })
+            .collect();
+
+        Ok(PStateReport {
+            driver: self.driver(),
+            status: self.status().ok(),
+            values: self.values().ok(),
+            turbo_pct: self.turbo_pct().ok().flatten(),
+            num_pstates: self.num_pstates().ok().flatten(),
+            cpus,
+        })
+    }
+}
+
+impl Display for PStateReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn or_unknown<T: Display>(value: &Option<T>) -> String {
+            value
+                .as_ref()
+                .map_or_else(|| "unknown".to_owned(), ToString::to_string)
+        }
+
+        writeln!(f, "driver: {}", self.driver)?;
+        writeln!(f, "status: {}", or_unknown(&self.status))?;
+        writeln!(f, "values: {}", or_unknown(&self.values))?;
+        writeln!(f, "turbo_pct: {}", or_unknown(&self.turbo_pct))?;
+        writeln!(f, "num_pstates: {}", or_unknown(&self.num_pstates))?;
+
+        for cpu in &self.cpus {
+            writeln!(
+                f,
+                "cpu{}: {} kHz, governor: {}, epp: {}",
+                cpu.cpu,
+                or_unknown(&cpu.cur_freq_khz),
+                or_unknown(&cpu.governor),
+                or_unknown(&cpu.epp)
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::*;
+    use crate::*;
+
+    #[test]
+    fn report_covers_two_cpus_and_leaves_out_missing_values() {
+        let mut files = VALUES.to_vec();
+        files.extend_from_slice(&[
+            ("intel_pstate/turbo_pct", "33\n"),
+            ("present", "0-1\n"),
+            ("cpu0/cpufreq/scaling_cur_freq", "2400000\n"),
+            ("cpu0/cpufreq/scaling_governor", "powersave\n"),
+            (
+                "cpu0/cpufreq/energy_performance_preference",
+                "balance_power\n",
+            ),
+            ("cpu1/cpufreq/scaling_cur_freq", "800000\n"),
+            ("cpu1/cpufreq/scaling_governor", "powersave\n"),
+        ]);
+        let (pstate, _) = mock(&files);
+
+        let report = pstate.report().unwrap();
+        assert_eq!(report.status, Some(PStateStatus::Active));
+        assert_eq!(report.values, Some(pstate.values().unwrap()));
+        assert_eq!(report.turbo_pct, Some(33));
+        assert_eq!(report.num_pstates, None);
+        assert_eq!(
+            report.cpus,
+            [
+                CpuReport {
+                    cpu: 0,
+                    cur_freq_khz: Some(2_400_000),
+                    governor: Some("powersave".to_owned()),
+                    epp: Some(Epp::BalancePower),
+                },
+                CpuReport {
+                    cpu: 1,
+                    cur_freq_khz: Some(800_000),
+                    governor: Some("powersave".to_owned()),
+                    epp: None,
+                },
+            ]
+        );
+
+        let text = report.to_string();
+        assert!(text.contains("num_pstates: unknown\n"), "{}", text);
+        assert!(
+            text.ends_with("cpu1: 800000 kHz, governor: powersave, epp: unknown\n"),
+            "{}",
+            text
+        );
+    }
+}
@@ -0,0 +1,63 @@
+// Copyright 2022 System76 <info@system76.com>
+// SPDX-License-Identifier: MIT
+
+//! Events describing each file that is read or written, emitted when the
+//! `tracing` feature is enabled.
+
+#[cfg(feature = "tracing")]
+use std::borrow::Cow;
+use std::{io, path::Path};
+
+/// The name of the attribute at `path`, such as `no_turbo`.
+#[cfg(feature = "tracing")]
+fn attr(path: &Path) -> Cow<'_, str> {
+    path.file_name()
+        .map_or(Cow::Borrowed(""), |name| name.to_string_lossy())
+}
+
+/// Whether a subscriber would record the event for a write, which is the only reason
+/// to read the old value before writing.
+#[cfg(feature = "tracing")]
+pub(crate) fn wants_old_value() -> bool {
+    tracing::enabled!(tracing::Level::INFO)
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn read(path: &Path, result: &io::Result<String>) {
+    match result {
+        Ok(value) => tracing::debug!(src = %attr(path), value = value.trim(), "read"),
+        Err(why) => tracing::debug!(src = %attr(path), error = %why, "failed to read"),
+    }
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn write(path: &Path, old: Option<&str>, new: &str, result: &io::Result<()>) {
+    match result {
+        Ok(()) => tracing::info!(
+            src = %attr(path),
+            old = old.map(str::trim),
+            new,
+            "wrote"
+        ),
+        Err(why) => tracing::warn!(src = %attr(path), new, error = %why, "failed to write"),
+    }
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn restore_failed(why: &crate::PStateError) {
+    tracing::warn!(error = %why, "failed to restore values on drop");
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn wants_old_value() -> bool {
+    false
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn read(_path: &Path, _result: &io::Result<String>) {}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn write(_path: &Path, _old: Option<&str>, _new: &str, _result: &io::Result<()>) {}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn restore_failed(_why: &crate::PStateError) {}
@@ -0,0 +1,168 @@
+// Copyright 2022 System76 <info@system76.com>
+// SPDX-License-Identifier: MIT
+
+//! Notification of changes made to the driver's attributes, using inotify.
+
+use crate::*;
+
+use inotify::{Inotify, WatchMask};
+
+/// How long the driver directory must be quiet before a change is reported.
+const DEBOUNCE: Duration = Duration::from_millis(50);
+
+const WATCH: &str = "watch";
+
+/// Reports the values of a [`PState`] each time they are changed.
+///
+/// Created with [`PState::watch`].
+pub struct Watcher {
+    pstate: PState,
+    inotify: Inotify,
+    values: PStateValues,
+}
+
+impl PState {
+    /// Watch the driver directory for changes to its values.
+    ///
+    /// The kernel only notifies of writes made from userspace, such as by another
+    /// tool or thermald. Limits the driver imposes on itself are not reported.
+    pub fn watch(&self) -> Result<Watcher, PStateError> {
+        let watch_error = |source| PStateError::GetValue {
+            src: WATCH,
+            path: self.path.clone(),
+            source,
+        };
+
+        let inotify = Inotify::init().map_err(watch_error)?;
+        inotify
+            .watches()
+            .add(&self.path, WatchMask::MODIFY | WatchMask::CLOSE_WRITE)
+            .map_err(watch_error)?;
+
+        Ok(Watcher {
+            pstate: self.clone(),
+            inotify,
+            values: self.values()?,
+        })
+    }
+}
+
+impl Watcher {
+    /// Block until the values change, returning the new values.
+    fn wait(&mut self) -> Result<PStateValues, PStateError> {
+        let mut buffer = [0; 1024];
+
+        loop {
+            self.inotify
+                .read_events_blocking(&mut buffer)
+                .map_err(|source| self.watch_error(source))?;
+
+            loop {
+                thread::sleep(DEBOUNCE);
+                match self.inotify.read_events(&mut buffer) {
+                    Ok(_) => continue,
+                    Err(why) if why.kind() == io::ErrorKind::WouldBlock => break,
+                    Err(why) => return Err(self.watch_error(why)),
+                }
+            }
+
+            let values = self.pstate.values()?;
+            if values != self.values {
+                self.values = values;
+                return Ok(values);
+            }
+        }
+    }
+
+    /// Convert into a stream of values, which waits for changes on a dedicated thread.
+    ///
+    /// The stream ends after yielding an error. Once the stream is dropped, the
+    /// thread exits after the next change is observed.
+    #[cfg(feature = "async")]
+    pub fn into_stream(mut self) -> WatchStream {
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+
+        thread::spawn(move || loop {
+            let result = self.wait();
+            let failed = result.is_err();
+            if tx.blocking_send(result).is_err() || failed {
+                break;
+            }
+        });
+
+        WatchStream { rx }
+    }
+
+    fn watch_error(&self, source: io::Error) -> PStateError {
+        PStateError::GetValue {
+            src: WATCH,
+            path: self.pstate.path.clone(),
+            source,
+        }
+    }
+}
+
+/// Blocks in `next` until the values change, yielding the new values.
+///
+/// Bursts of writes are coalesced, so a `set_values` call made by another process
+/// is reported once. Writes which leave the values as they were are not reported.
+/// The iterator never ends by itself.
+impl Iterator for Watcher {
+    type Item = Result<PStateValues, PStateError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.wait())
+    }
+}
+
+/// A stream of the values reported by a [`Watcher`].
+///
+/// Created with [`Watcher::into_stream`].
+#[cfg(feature = "async")]
+pub struct WatchStream {
+    rx: tokio::sync::mpsc::Receiver<Result<PStateValues, PStateError>>,
+}
+
+#[cfg(feature = "async")]
+impl futures_core::Stream for WatchStream {
+    type Item = Result<PStateValues, PStateError>;
+
+    fn poll_next(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Self::Item>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::*;
+    use crate::*;
+
+    use std::fs;
+
+    #[test]
+    fn watcher_reports_only_changed_values() {
+        let dir = tempfile::tempdir().unwrap();
+        let driver = dir.path().join("intel_pstate");
+        fs::create_dir(&driver).unwrap();
+        for &(file, value) in VALUES {
+            fs::write(dir.path().join(file), value).unwrap();
+        }
+
+        let pstate = PState::with_path(&driver).unwrap();
+        let mut watcher = pstate.watch().unwrap();
+
+        let writer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            fs::write(driver.join("max_perf_pct"), "80\n").unwrap();
+            thread::sleep(Duration::from_millis(100));
+            fs::write(driver.join("max_perf_pct"), "60\n").unwrap();
+        });
+
+        let values = watcher.next().unwrap().unwrap();
+        assert_eq!(values.max_perf_pct, pct(60));
+        writer.join().unwrap();
+    }
+}